@@ -0,0 +1,179 @@
+//! Finds the container a process runs in from its cgroup, so a listener in a
+//! `--network host` container is attributed even though it publishes nothing.
+//! When the cgroup path doesn't name a container (some cgroupfs layouts), a
+//! process in another mount namespace is matched by the per-container
+//! `hostname` file every runtime bind-mounts into it.
+
+use crate::runtime::{is_container_id, RuntimeKind};
+use std::{fs, path::Path};
+
+/// The runtime and full container ID of the container `pid` runs in.
+pub fn container_of(pid: u32) -> Option<(RuntimeKind, String)> {
+    let proc_dir = Path::new("/proc").join(pid.to_string());
+    if let Some(found) = fs::read_to_string(proc_dir.join("cgroup"))
+        .ok()
+        .and_then(|cgroup| from_cgroup(&cgroup))
+    {
+        return Some(found);
+    }
+    let own = fs::read_link("/proc/self/ns/mnt").ok()?;
+    if fs::read_link(proc_dir.join("ns/mnt")).ok()? == own {
+        return None;
+    }
+    from_mountinfo(&fs::read_to_string(proc_dir.join("mountinfo")).ok()?)
+}
+
+/// Host pids of every process running in container `id`.
+pub fn processes_in(id: &str) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .filter(|&pid| container_of(pid).is_some_and(|(_, found)| found == id))
+        .collect()
+}
+
+/// Reads a container ID out of `/proc/<pid>/cgroup`, which holds one
+/// `hierarchy:controllers:path` line per hierarchy (one `0::path` on cgroup
+/// v2). Kubernetes pods are left alone: the kubelet, not a runtime we drive,
+/// would restart them.
+fn from_cgroup(cgroup: &str) -> Option<(RuntimeKind, String)> {
+    cgroup.lines().find_map(|line| {
+        let path = line.splitn(3, ':').nth(2)?;
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        if segments
+            .iter()
+            .any(|segment| segment.starts_with("kubepods"))
+        {
+            return None;
+        }
+        segments.iter().enumerate().rev().find_map(|(i, segment)| {
+            let unit = segment.strip_suffix(".scope").unwrap_or(segment);
+            let scoped = [
+                ("docker-", RuntimeKind::Docker),
+                ("libpod-", RuntimeKind::Podman),
+                ("nerdctl-", RuntimeKind::Nerdctl),
+            ];
+            for (prefix, runtime) in scoped {
+                if let Some(id) = unit.strip_prefix(prefix).filter(|id| is_container_id(id)) {
+                    return Some((runtime, id.to_string()));
+                }
+            }
+            // cgroupfs driver: /docker/<id>, /libpod_parent/libpod-<id>, or
+            // /default/<id> for nerdctl's default containerd namespace. Any
+            // other parent belongs to something we don't manage.
+            if !is_container_id(unit) {
+                return None;
+            }
+            let runtime = match (i, segments[..i].last()) {
+                (_, Some(&"docker")) => RuntimeKind::Docker,
+                (_, Some(parent)) if parent.starts_with("libpod") => RuntimeKind::Podman,
+                (1, Some(&"default")) => RuntimeKind::Nerdctl,
+                _ => return None,
+            };
+            Some((runtime, unit.to_string()))
+        })
+    })
+}
+
+/// Finds the runtime's per-container `hostname` file among the mount roots in
+/// `/proc/<pid>/mountinfo`.
+fn from_mountinfo(mountinfo: &str) -> Option<(RuntimeKind, String)> {
+    mountinfo.lines().find_map(|line| {
+        let root = line.split(' ').nth(3)?;
+        let dir = root.strip_suffix("/hostname")?;
+        let dir = dir.strip_suffix("/userdata").unwrap_or(dir);
+        let (parent, id) = dir.rsplit_once('/')?;
+        if !is_container_id(id) {
+            return None;
+        }
+        let runtime = if parent.ends_with("/overlay-containers") {
+            RuntimeKind::Podman
+        } else if parent.contains("/nerdctl/") && parent.ends_with("/containers/default") {
+            RuntimeKind::Nerdctl
+        } else if parent.ends_with("/containers") {
+            RuntimeKind::Docker
+        } else {
+            return None;
+        };
+        Some((runtime, id.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "3f4e0a1c9b2d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f";
+
+    #[test]
+    fn reads_container_ids_from_cgroup_paths() {
+        let found = |cgroup: &str| from_cgroup(&cgroup.replace("ID", ID));
+
+        assert_eq!(
+            found("0::/system.slice/docker-ID.scope\n"),
+            Some((RuntimeKind::Docker, ID.to_string()))
+        );
+        assert_eq!(
+            found("12:memory:/docker/ID\n11:pids:/docker/ID\n"),
+            Some((RuntimeKind::Docker, ID.to_string()))
+        );
+        assert_eq!(
+            found("0::/user.slice/user-1000.slice/user@1000.service/user.slice/libpod-ID.scope/container\n"),
+            Some((RuntimeKind::Podman, ID.to_string()))
+        );
+        assert_eq!(
+            found("0::/default/ID\n"),
+            Some((RuntimeKind::Nerdctl, ID.to_string()))
+        );
+        // Kubernetes pods and containers in other containerd namespaces
+        // aren't ours to stop.
+        assert_eq!(
+            found("0::/kubepods.slice/kubepods-besteffort.slice/cri-containerd-ID.scope\n"),
+            None
+        );
+        assert_eq!(found("0::/kubepods/besteffort/podabc/ID\n"), None);
+        assert_eq!(found("0::/k8s.io/ID\n"), None);
+        assert_eq!(found("0::/machine.slice/ID\n"), None);
+        // Podman's monitor process and the Docker daemon itself are on the host.
+        assert_eq!(found("0::/user.slice/libpod-conmon-ID.scope\n"), None);
+        assert_eq!(found("0::/system.slice/docker.service\n"), None);
+        assert_eq!(
+            found("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_container_ids_from_hostname_mounts() {
+        let line = |root: &str| {
+            format!(
+                "1460 1441 0:52 {} /etc/hostname rw,relatime - ext4 /dev/vda1 rw\n",
+                root.replace("ID", ID)
+            )
+        };
+        let found = |root: &str| from_mountinfo(&format!("{}{}", line("/"), line(root)));
+
+        assert_eq!(
+            found("/var/lib/docker/containers/ID/hostname"),
+            Some((RuntimeKind::Docker, ID.to_string()))
+        );
+        assert_eq!(
+            found(
+                "/home/me/.local/share/containers/storage/overlay-containers/ID/userdata/hostname"
+            ),
+            Some((RuntimeKind::Podman, ID.to_string()))
+        );
+        assert_eq!(
+            found("/var/lib/nerdctl/1935db59/containers/default/ID/hostname"),
+            Some((RuntimeKind::Nerdctl, ID.to_string()))
+        );
+        assert_eq!(
+            found("/var/lib/nerdctl/1935db59/containers/k8s.io/ID/hostname"),
+            None
+        );
+        assert_eq!(found("/etc/hostname"), None);
+    }
+}
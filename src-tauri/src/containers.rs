@@ -0,0 +1,315 @@
+//! Attributes listeners to the containers behind them, whether a container
+//! publishes the port, runs the listening process, or sits behind the userland
+//! proxy that holds it, and picks the containers a disconnect stops.
+
+use crate::{
+    proxies::Proxy,
+    runtime::{self, ComposeInfo, Container, PortBinding, RuntimeError, RuntimeKind},
+    ContainerScope, Protocol,
+};
+use serde::Serialize;
+use std::{collections::HashMap, thread, time::Duration};
+
+/// A container as a listener is attributed to it.
+#[derive(Debug, Clone)]
+pub struct PublishedContainer {
+    pub id: String,
+    pub name: String,
+    pub runtime: RuntimeKind,
+    pub compose: Option<ComposeInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnavailableRuntime {
+    pub runtime: RuntimeKind,
+    pub message: String,
+}
+
+/// Running containers across every container runtime that answers within
+/// `timeout`, and the runtimes that are set up but refused, failed or didn't
+/// answer. Runtimes that aren't installed are left out quietly. They are
+/// asked at the same time, so a hung one costs at most one timeout.
+pub fn running_containers(
+    timeout: Duration,
+) -> (Vec<(RuntimeKind, Container)>, Vec<UnavailableRuntime>) {
+    let runtimes = runtime::runtimes(timeout);
+    let answers: Vec<_> = thread::scope(|scope| {
+        let asked: Vec<_> = runtimes
+            .iter()
+            .map(|runtime| {
+                scope.spawn(move || match runtime {
+                    Ok(runtime) => (runtime.kind(), runtime.containers()),
+                    Err((kind, e)) => (*kind, Err(RuntimeError::Unavailable(e.to_string()))),
+                })
+            })
+            .collect();
+        asked
+            .into_iter()
+            .filter_map(|answer| answer.join().ok())
+            .collect()
+    });
+
+    let mut running = Vec::new();
+    let mut unavailable = Vec::new();
+    for (kind, answer) in answers {
+        match answer {
+            Ok(containers) => running.extend(containers.into_iter().map(|c| (kind, c))),
+            Err(e) => unavailable.push(UnavailableRuntime {
+                runtime: kind,
+                message: e.to_string(),
+            }),
+        }
+    }
+    (running, unavailable)
+}
+
+/// The container `pid` runs in.
+pub fn process_container(
+    pid: u32,
+    running: &[(RuntimeKind, Container)],
+) -> Option<PublishedContainer> {
+    let (runtime, id) = container_of_pid(pid)?;
+    Some(identify_container(runtime, id, running))
+}
+
+/// The container a userland proxy forwards to.
+pub fn proxied_container(
+    proxy: &Proxy,
+    running: &[(RuntimeKind, Container)],
+) -> Option<PublishedContainer> {
+    if let Some((runtime, container)) = proxy.container(running) {
+        return Some(identify_container(*runtime, container.id.clone(), running));
+    }
+    // Only Podman passes slirp4netns the container ID.
+    let id = proxy.container_id.clone()?;
+    Some(identify_container(RuntimeKind::Podman, id, running))
+}
+
+/// Names container `id` from the runtime that reports it. A container no
+/// runtime answered for (its socket is elsewhere, say) is still identified, by
+/// its short ID.
+fn identify_container(
+    runtime: RuntimeKind,
+    id: String,
+    running: &[(RuntimeKind, Container)],
+) -> PublishedContainer {
+    // Matched by ID alone: Podman's Docker-compatible socket reports its
+    // containers as Docker's.
+    match running.iter().find(|(_, container)| container.id == id) {
+        Some((runtime, container)) => PublishedContainer {
+            id,
+            name: container.name.clone(),
+            runtime: *runtime,
+            compose: container.compose.clone(),
+        },
+        None => PublishedContainer {
+            name: id[..12].to_string(),
+            id,
+            runtime,
+            compose: None,
+        },
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn container_of_pid(pid: u32) -> Option<(RuntimeKind, String)> {
+    crate::cgroups::container_of(pid)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn container_of_pid(_pid: u32) -> Option<(RuntimeKind, String)> {
+    None
+}
+
+/// Host pids of the processes in container `id`. Only Linux runs containers
+/// as host processes; elsewhere they live in a VM and none are returned.
+#[cfg(target_os = "linux")]
+pub fn container_processes(id: &str) -> Vec<u32> {
+    crate::cgroups::processes_in(id)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn container_processes(_id: &str) -> Vec<u32> {
+    Vec::new()
+}
+
+/// Keeps every binding of a host port, ordered by container then host IP, so
+/// containers publishing the same port on different IPs are all attributed.
+pub fn group_by_host_port(
+    running: Vec<(RuntimeKind, Container)>,
+) -> HashMap<(u16, Protocol), Vec<(PublishedContainer, PortBinding)>> {
+    let mut published: HashMap<_, Vec<(PublishedContainer, PortBinding)>> = HashMap::new();
+    for (runtime, container) in running {
+        let owner = PublishedContainer {
+            id: container.id,
+            name: container.name,
+            runtime,
+            compose: container.compose,
+        };
+        for binding in container.ports {
+            if let Some(port) = binding.host_port {
+                published
+                    .entry((port, binding.protocol))
+                    .or_default()
+                    .push((owner.clone(), binding));
+            }
+        }
+    }
+    for bindings in published.values_mut() {
+        bindings.sort_by(|(a, x), (b, y)| (&a.name, x.host_ip).cmp(&(&b.name, y.host_ip)));
+    }
+    published
+}
+
+/// The running containers a disconnect of `container` stops: just that one,
+/// or every replica of its compose service, or its whole compose project. A
+/// container Compose didn't start is stopped alone whatever the scope.
+pub fn containers_to_stop(
+    running: Vec<Container>,
+    container: &PublishedContainer,
+    scope: ContainerScope,
+) -> Result<Vec<Container>, String> {
+    let compose = match scope {
+        ContainerScope::Container => None,
+        ContainerScope::Service | ContainerScope::Project => container.compose.as_ref(),
+    };
+    let targets: Vec<Container> = running
+        .into_iter()
+        .filter(|candidate| match (compose, &candidate.compose) {
+            (None, _) => candidate.id == container.id,
+            (Some(target), Some(candidate)) => {
+                target.project == candidate.project
+                    && (scope == ContainerScope::Project || target.service == candidate.service)
+            }
+            (Some(_), None) => false,
+        })
+        .collect();
+    if targets.is_empty() {
+        return Err(format!("container {} is no longer running", container.name));
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ListenerInfo;
+    use std::net::IpAddr;
+
+    fn compose(project: &str, service: &str) -> Option<ComposeInfo> {
+        Some(ComposeInfo {
+            project: project.to_string(),
+            service: service.to_string(),
+            working_dir: None,
+        })
+    }
+
+    #[test]
+    fn keeps_every_container_publishing_a_host_port() {
+        let published = group_by_host_port(vec![
+            (
+                RuntimeKind::Podman,
+                Container {
+                    ports: vec![PortBinding::new(
+                        Some("192.168.1.20"),
+                        Some(7777),
+                        8080,
+                        Protocol::Tcp,
+                    )],
+                    ..Container::named("web-b")
+                },
+            ),
+            (
+                RuntimeKind::Docker,
+                Container {
+                    ports: vec![
+                        PortBinding::new(Some("127.0.0.1"), Some(7777), 8080, Protocol::Tcp),
+                        PortBinding::new(Some("127.0.0.1"), Some(7777), 53, Protocol::Udp),
+                        PortBinding::new(None, None, 9000, Protocol::Tcp),
+                    ],
+                    ..Container::named("web-a")
+                },
+            ),
+        ]);
+
+        let mut listener =
+            ListenerInfo::new(7777, Protocol::Tcp, "127.0.0.1".parse::<IpAddr>().unwrap());
+        listener.attach_containers(&published[&(7777, Protocol::Tcp)]);
+        let bindings: Vec<_> = listener
+            .container_bindings
+            .iter()
+            .map(|b| {
+                (
+                    b.container_name.as_str(),
+                    b.runtime,
+                    b.binding.host_ip.unwrap().to_string(),
+                    b.binding.container_port,
+                )
+            })
+            .collect();
+        assert_eq!(
+            bindings,
+            [
+                ("web-a", RuntimeKind::Docker, "127.0.0.1".to_string(), 8080),
+                (
+                    "web-b",
+                    RuntimeKind::Podman,
+                    "192.168.1.20".to_string(),
+                    8080
+                ),
+            ]
+        );
+        assert_eq!(listener.container_name.as_deref(), Some("web-a"));
+        assert_eq!(published[&(7777, Protocol::Udp)].len(), 1);
+        assert_eq!(published.len(), 2);
+    }
+
+    #[test]
+    fn stops_a_container_its_compose_service_or_its_project() {
+        let running = vec![
+            Container {
+                compose: compose("shop", "web"),
+                ..Container::named("web-1")
+            },
+            Container {
+                compose: compose("shop", "web"),
+                ..Container::named("web-2")
+            },
+            Container {
+                compose: compose("shop", "db"),
+                ..Container::named("db-1")
+            },
+            Container {
+                compose: compose("blog", "web"),
+                ..Container::named("other-web-1")
+            },
+            Container::named("loose"),
+        ];
+        let target = PublishedContainer {
+            id: "web-1".to_string(),
+            name: "web-1".to_string(),
+            runtime: RuntimeKind::Docker,
+            compose: running[0].compose.clone(),
+        };
+        let stopped = |scope| {
+            containers_to_stop(running.clone(), &target, scope)
+                .unwrap()
+                .into_iter()
+                .map(|c| c.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(stopped(ContainerScope::Container), ["web-1"]);
+        assert_eq!(stopped(ContainerScope::Service), ["web-1", "web-2"]);
+        assert_eq!(stopped(ContainerScope::Project), ["web-1", "web-2", "db-1"]);
+
+        let loose = PublishedContainer {
+            id: "loose".to_string(),
+            name: "loose".to_string(),
+            runtime: RuntimeKind::Docker,
+            compose: None,
+        };
+        let stopped = containers_to_stop(running, &loose, ContainerScope::Project).unwrap();
+        assert_eq!(stopped, [Container::named("loose")]);
+    }
+}
@@ -0,0 +1,649 @@
+//! A minimal client for the Docker Engine API, spoken directly over the
+//! daemon's socket so containers are found without the `docker` CLI. Podman's
+//! API service answers the same requests. The Docker daemon is located the way
+//! the CLI does it: `DOCKER_HOST`, then `DOCKER_CONTEXT`, then the current
+//! context in the CLI config.
+
+use crate::{
+    runtime::{network_addresses, ComposeInfo, Container, PortBinding},
+    Protocol,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How long a request gets unless the caller sets a timeout.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_TCP_PORT: u16 = 2375;
+
+/// Endpoints with an abandoned exchange still in flight. A named pipe has no
+/// read timeout, so such an exchange may never end; until it does, the
+/// endpoint isn't asked again and no more threads pile up waiting on it.
+static STALLED: Mutex<Vec<Endpoint>> = Mutex::new(Vec::new());
+
+/// Where the daemon listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Unix(PathBuf),
+    Tcp(String),
+    NamedPipe(PathBuf),
+}
+
+impl Endpoint {
+    /// Whether a local socket or pipe is there; a TCP address can only be
+    /// tried.
+    pub fn exists(&self) -> bool {
+        match self {
+            Endpoint::Unix(path) | Endpoint::NamedPipe(path) => path.exists(),
+            Endpoint::Tcp(_) => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DockerError {
+    /// No daemon could be reached at the endpoint, or none is configured in a
+    /// way we can use.
+    Unavailable(String),
+    /// The daemon answered with an error status.
+    Api {
+        status: u16,
+        message: String,
+    },
+    InvalidResponse(String),
+    /// The daemon accepted the request but didn't answer in time, as a
+    /// half-started Docker Desktop or colima VM does.
+    TimedOut(String),
+}
+
+impl fmt::Display for DockerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DockerError::Unavailable(message) => write!(f, "Docker is unavailable: {message}"),
+            DockerError::Api { status, message } => write!(f, "{message} (HTTP {status})"),
+            DockerError::InvalidResponse(message) => {
+                write!(f, "unexpected response from Docker: {message}")
+            }
+            DockerError::TimedOut(message) => write!(f, "Docker is not responding: {message}"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiContainer {
+    id: String,
+    #[serde(default)]
+    names: Vec<String>,
+    #[serde(default)]
+    ports: Vec<ApiPort>,
+    #[serde(default)]
+    labels: Option<HashMap<String, String>>,
+    #[serde(default)]
+    network_settings: Option<ApiNetworkSettings>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiNetworkSettings {
+    #[serde(default)]
+    networks: Option<HashMap<String, ApiNetwork>>,
+}
+
+#[derive(Deserialize)]
+struct ApiNetwork {
+    #[serde(rename = "IPAddress", default)]
+    ip_address: Option<String>,
+    #[serde(rename = "GlobalIPv6Address", default)]
+    global_ipv6_address: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiPort {
+    #[serde(rename = "IP")]
+    ip: Option<String>,
+    #[serde(rename = "PrivatePort")]
+    private_port: u16,
+    #[serde(rename = "PublicPort")]
+    public_port: Option<u16>,
+    #[serde(rename = "Type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+pub struct Docker {
+    endpoint: Endpoint,
+    timeout: Duration,
+}
+
+impl Docker {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self {
+            endpoint,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Gives up on requests the daemon hasn't answered within `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The daemon the `docker` CLI would talk to.
+    pub fn from_env() -> Result<Self, DockerError> {
+        let config_dir = env::var_os("DOCKER_CONFIG")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".docker")));
+        resolve_endpoint(
+            env::var("DOCKER_HOST").ok().as_deref(),
+            env::var("DOCKER_CONTEXT").ok().as_deref(),
+            config_dir.as_deref(),
+        )
+        .map(Self::new)
+    }
+
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Whether this is the platform's default socket, which the CLI falls
+    /// back to when no host or context is set.
+    pub fn is_default(&self) -> bool {
+        self.endpoint == default_endpoint()
+    }
+
+    /// Running containers.
+    pub fn containers(&self) -> Result<Vec<Container>, DockerError> {
+        let body = self.request("GET", "/containers/json".to_string(), self.timeout)?;
+        let containers: Vec<ApiContainer> = serde_json::from_slice(&body)
+            .map_err(|e| DockerError::InvalidResponse(e.to_string()))?;
+        Ok(containers.into_iter().map(Container::from).collect())
+    }
+
+    /// Stops a container, killing it if it is still running after
+    /// `timeout_secs`. Stopping one that already stopped succeeds.
+    pub fn stop(&self, id: &str, timeout_secs: u32) -> Result<(), DockerError> {
+        // The daemon only answers once the container is down.
+        let timeout = self.timeout + Duration::from_secs(timeout_secs.into());
+        self.request(
+            "POST",
+            format!("/containers/{id}/stop?t={timeout_secs}"),
+            timeout,
+        )
+        .map(drop)
+    }
+
+    pub fn kill(&self, id: &str) -> Result<(), DockerError> {
+        self.request("POST", format!("/containers/{id}/kill"), self.timeout)
+            .map(drop)
+    }
+
+    /// Starts a container. Starting one that is already running succeeds.
+    pub fn start(&self, id: &str) -> Result<(), DockerError> {
+        self.request("POST", format!("/containers/{id}/start"), self.timeout)
+            .map(drop)
+    }
+
+    /// Sends one request on a fresh connection and returns the body of a
+    /// successful (2xx or 304) response, or gives up after `timeout`.
+    fn request(
+        &self,
+        method: &'static str,
+        path: String,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, DockerError> {
+        if STALLED.lock().unwrap().contains(&self.endpoint) {
+            return Err(DockerError::TimedOut(format!(
+                "{} has not answered an earlier request",
+                self.endpoint
+            )));
+        }
+        // The exchange runs on its own thread so that nothing, not even a
+        // named pipe, holds the caller past `timeout`. Whether it was
+        // abandoned is settled under the `STALLED` lock, so the endpoint is
+        // released exactly when an abandoned exchange finishes.
+        let (done, response) = mpsc::channel();
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let endpoint = self.endpoint.clone();
+        let given_up = Arc::clone(&abandoned);
+        thread::spawn(move || {
+            let raw = exchange(&endpoint, method, &path, timeout);
+            let mut stalled = STALLED.lock().unwrap();
+            if given_up.load(Ordering::Relaxed) {
+                if let Some(i) = stalled.iter().position(|e| *e == endpoint) {
+                    stalled.swap_remove(i);
+                }
+            } else {
+                let _ = done.send(raw);
+            }
+        });
+        let raw = match response.recv_timeout(timeout) {
+            Ok(raw) => raw,
+            Err(_) => {
+                let mut stalled = STALLED.lock().unwrap();
+                match response.try_recv() {
+                    // It finished while the lock was being taken.
+                    Ok(raw) => raw,
+                    Err(_) => {
+                        abandoned.store(true, Ordering::Relaxed);
+                        stalled.push(self.endpoint.clone());
+                        return Err(DockerError::TimedOut(format!(
+                            "{} did not answer within {} ms",
+                            self.endpoint,
+                            timeout.as_millis()
+                        )));
+                    }
+                }
+            }
+        };
+        let raw = raw.map_err(|e| DockerError::Unavailable(format!("{}: {e}", self.endpoint)))?;
+
+        let (status, body) = parse_response(&raw)?;
+        if (200..300).contains(&status) || status == 304 {
+            return Ok(body);
+        }
+        let message = serde_json::from_slice::<ApiError>(&body)
+            .map(|error| error.message)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&body).trim().to_string());
+        Err(DockerError::Api { status, message })
+    }
+}
+
+/// Writes one request and reads the raw response to EOF.
+fn exchange(
+    endpoint: &Endpoint,
+    method: &str,
+    path: &str,
+    timeout: Duration,
+) -> io::Result<Vec<u8>> {
+    let mut stream = endpoint.connect(timeout)?;
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: docker\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw)?;
+    Ok(raw)
+}
+
+impl Endpoint {
+    fn connect(&self, timeout: Duration) -> io::Result<Box<dyn Stream>> {
+        match self {
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                let stream = std::os::unix::net::UnixStream::connect(path)?;
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(not(unix))]
+            Endpoint::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            )),
+            Endpoint::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                Ok(Box::new(stream))
+            }
+            Endpoint::NamedPipe(path) => Ok(Box::new(
+                fs::OpenOptions::new().read(true).write(true).open(path)?,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Unix(path) => write!(f, "unix://{}", path.display()),
+            Endpoint::Tcp(address) => write!(f, "tcp://{address}"),
+            Endpoint::NamedPipe(path) => write!(f, "npipe://{}", path.display()),
+        }
+    }
+}
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+impl From<ApiContainer> for Container {
+    fn from(container: ApiContainer) -> Self {
+        let name = container
+            .names
+            .first()
+            .map(|name| name.trim_start_matches('/').to_string())
+            .unwrap_or_else(|| container.id.chars().take(12).collect());
+        let ports = container
+            .ports
+            .into_iter()
+            .filter_map(|port| {
+                let protocol = match port.kind.as_str() {
+                    "tcp" => Protocol::Tcp,
+                    "udp" => Protocol::Udp,
+                    _ => return None,
+                };
+                Some(PortBinding {
+                    host_ip: port.ip.and_then(|ip| ip.parse().ok()),
+                    host_port: port.public_port.filter(|&port| port != 0),
+                    container_port: port.private_port,
+                    protocol,
+                })
+            })
+            .collect();
+        let addresses = network_addresses(
+            container
+                .network_settings
+                .iter()
+                .flat_map(|settings| settings.networks.iter().flatten())
+                .map(|(_, network)| (&network.ip_address, &network.global_ipv6_address)),
+        );
+        Container {
+            compose: container.labels.as_ref().and_then(ComposeInfo::from_labels),
+            id: container.id,
+            name,
+            ports,
+            addresses,
+        }
+    }
+}
+
+/// `DOCKER_HOST` wins, then the named context, then the CLI's current context,
+/// then the platform default.
+fn resolve_endpoint(
+    docker_host: Option<&str>,
+    context: Option<&str>,
+    config_dir: Option<&Path>,
+) -> Result<Endpoint, DockerError> {
+    if let Some(host) = docker_host.filter(|host| !host.is_empty()) {
+        return parse_host(host);
+    }
+    let context = context
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .or_else(|| {
+            let config = fs::read(config_dir?.join("config.json")).ok()?;
+            let config: serde_json::Value = serde_json::from_slice(&config).ok()?;
+            config.get("currentContext")?.as_str().map(str::to_string)
+        });
+    match context.filter(|c| c != "default") {
+        Some(name) => {
+            let host = config_dir
+                .and_then(|dir| context_host(dir, &name))
+                .ok_or_else(|| {
+                    DockerError::Unavailable(format!("docker context {name:?} was not found"))
+                })?;
+            parse_host(&host)
+        }
+        None => Ok(default_endpoint()),
+    }
+}
+
+/// The daemon address stored for context `name`. Context directories are
+/// named by a hash of the name, so each one's metadata is checked instead.
+fn context_host(config_dir: &Path, name: &str) -> Option<String> {
+    fs::read_dir(config_dir.join("contexts/meta"))
+        .ok()?
+        .flatten()
+        .find_map(|entry| {
+            let meta = fs::read(entry.path().join("meta.json")).ok()?;
+            let meta: serde_json::Value = serde_json::from_slice(&meta).ok()?;
+            if meta.get("Name")?.as_str()? != name {
+                return None;
+            }
+            meta.pointer("/Endpoints/docker/Host")?
+                .as_str()
+                .map(str::to_string)
+        })
+}
+
+/// Parses a `DOCKER_HOST`-style address.
+pub fn parse_host(host: &str) -> Result<Endpoint, DockerError> {
+    if let Some(path) = host.strip_prefix("unix://") {
+        return Ok(Endpoint::Unix(PathBuf::from(path)));
+    }
+    if let Some(path) = host.strip_prefix("npipe://") {
+        return Ok(Endpoint::NamedPipe(PathBuf::from(path.replace('/', "\\"))));
+    }
+    if let Some(address) = host
+        .strip_prefix("tcp://")
+        .or_else(|| host.strip_prefix("http://"))
+    {
+        let address = address.trim_end_matches('/');
+        let has_port = address
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+        return Ok(Endpoint::Tcp(if has_port {
+            address.to_string()
+        } else {
+            format!("{address}:{DEFAULT_TCP_PORT}")
+        }));
+    }
+    Err(DockerError::Unavailable(format!(
+        "unsupported host {host:?}; only unix://, tcp:// and npipe:// are supported"
+    )))
+}
+
+#[cfg(windows)]
+fn default_endpoint() -> Endpoint {
+    Endpoint::NamedPipe(PathBuf::from(r"\\.\pipe\docker_engine"))
+}
+
+/// The system socket, or Docker Desktop's and rootless Docker's per-user
+/// sockets when that one doesn't exist.
+#[cfg(not(windows))]
+fn default_endpoint() -> Endpoint {
+    let system = PathBuf::from("/var/run/docker.sock");
+    let per_user = [
+        home_dir().map(|home| home.join(".docker/run/docker.sock")),
+        env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("docker.sock")),
+    ];
+    let path = std::iter::once(system.clone())
+        .chain(per_user.into_iter().flatten())
+        .find(|path| path.exists())
+        .unwrap_or(system);
+    Endpoint::Unix(path)
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from)
+}
+
+/// Splits a raw HTTP/1.1 response read to EOF into its status and body.
+fn parse_response(raw: &[u8]) -> Result<(u16, Vec<u8>), DockerError> {
+    let invalid = |message: &str| DockerError::InvalidResponse(message.to_string());
+    let head_end = find(raw, b"\r\n\r\n").ok_or_else(|| invalid("missing headers"))?;
+    let head = String::from_utf8_lossy(&raw[..head_end]);
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| invalid("missing status"))?;
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding")
+                && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+
+    let body = &raw[head_end + 4..];
+    if !chunked {
+        return Ok((status, body.to_vec()));
+    }
+    let mut rest = body;
+    let mut body = Vec::new();
+    loop {
+        let line_end = find(rest, b"\r\n").ok_or_else(|| invalid("truncated chunk"))?;
+        let size = std::str::from_utf8(&rest[..line_end])
+            .ok()
+            .and_then(|line| line.split(';').next())
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+            .ok_or_else(|| invalid("bad chunk size"))?;
+        rest = &rest[line_end + 2..];
+        if size == 0 {
+            return Ok((status, body));
+        }
+        if rest.len() < size + 2 {
+            return Err(invalid("truncated chunk"));
+        }
+        body.extend_from_slice(&rest[..size]);
+        rest = &rest[size + 2..];
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn lists_containers_from_a_daemon_socket() {
+        use std::os::unix::net::UnixListener;
+
+        let socket =
+            env::temp_dir().join(format!("port-o-potty-docker-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let daemon = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let len = stream.read(&mut request).unwrap();
+            let body = r#"[{"Id":"4f1c0ffee","Names":["/web"],"Ports":[
+                {"IP":"0.0.0.0","PrivatePort":80,"PublicPort":8080,"Type":"tcp"},
+                {"IP":"::","PrivatePort":80,"PublicPort":8080,"Type":"tcp"},
+                {"PrivatePort":443,"Type":"tcp"},
+                {"PrivatePort":9000,"PublicPort":9000,"Type":"sctp"}],
+                "Labels":{"com.docker.compose.project":"shop",
+                    "com.docker.compose.service":"web",
+                    "com.docker.compose.project.working_dir":"/src/shop"},
+                "NetworkSettings":{"Networks":{"shop_default":{
+                    "IPAddress":"172.18.0.3","GlobalIPv6Address":""}}}}]"#;
+            let (first, second) = body.split_at(40);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n\
+                 {:x}\r\n{first}\r\n{:x}\r\n{second}\r\n0\r\n\r\n",
+                first.len(),
+                second.len()
+            )
+            .unwrap();
+            String::from_utf8_lossy(&request[..len]).to_string()
+        });
+
+        let containers = Docker::new(Endpoint::Unix(socket.clone()))
+            .containers()
+            .unwrap();
+        let request = daemon.join().unwrap();
+        fs::remove_file(&socket).unwrap();
+
+        assert!(request.starts_with("GET /containers/json HTTP/1.1\r\n"));
+        assert_eq!(
+            containers,
+            [Container {
+                id: "4f1c0ffee".to_string(),
+                name: "web".to_string(),
+                ports: vec![
+                    PortBinding::new(Some("0.0.0.0"), Some(8080), 80, Protocol::Tcp),
+                    PortBinding::new(Some("::"), Some(8080), 80, Protocol::Tcp),
+                    PortBinding::new(None, None, 443, Protocol::Tcp),
+                ],
+                compose: Some(ComposeInfo {
+                    project: "shop".to_string(),
+                    service: "web".to_string(),
+                    working_dir: Some("/src/shop".to_string()),
+                }),
+                addresses: vec!["172.18.0.3".parse().unwrap()],
+            }]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gives_up_on_a_daemon_that_never_answers() {
+        use std::{os::unix::net::UnixListener, time::Instant};
+
+        let socket = env::temp_dir().join(format!(
+            "port-o-potty-docker-hung-{}.sock",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let started = Instant::now();
+        let result = Docker::new(Endpoint::Unix(socket.clone()))
+            .with_timeout(Duration::from_millis(200))
+            .containers();
+        drop(listener);
+        fs::remove_file(&socket).unwrap();
+
+        assert!(matches!(result, Err(DockerError::TimedOut(_))));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn reports_the_daemons_error_message() {
+        let raw = b"HTTP/1.1 404 Not Found\r\nContent-Length: 39\r\n\r\n{\"message\":\"No such container: gone\"}\n";
+        let (status, body) = parse_response(raw).unwrap();
+        assert_eq!(status, 404);
+        assert_eq!(
+            serde_json::from_slice::<ApiError>(&body).unwrap().message,
+            "No such container: gone"
+        );
+    }
+
+    #[test]
+    fn finds_the_daemon_from_docker_host_and_contexts() {
+        let config = env::temp_dir().join(format!("port-o-potty-docker-{}", std::process::id()));
+        let meta = config.join("contexts/meta/0c1f2e");
+        fs::create_dir_all(&meta).unwrap();
+        fs::write(config.join("config.json"), r#"{"currentContext":"colima"}"#).unwrap();
+        fs::write(
+            meta.join("meta.json"),
+            r#"{"Name":"colima","Endpoints":{"docker":{"Host":"unix:///home/me/.colima/docker.sock"}}}"#,
+        )
+        .unwrap();
+
+        let from_host = resolve_endpoint(Some("tcp://10.0.0.5"), Some("colima"), Some(&config));
+        let from_context = resolve_endpoint(None, None, Some(&config));
+        let missing = resolve_endpoint(None, Some("gone"), Some(&config));
+        fs::remove_dir_all(&config).unwrap();
+
+        assert_eq!(
+            from_host.unwrap(),
+            Endpoint::Tcp("10.0.0.5:2375".to_string())
+        );
+        assert_eq!(
+            from_context.unwrap(),
+            Endpoint::Unix(PathBuf::from("/home/me/.colima/docker.sock"))
+        );
+        assert!(matches!(missing, Err(DockerError::Unavailable(_))));
+        assert_eq!(
+            parse_host("npipe:////./pipe/docker_engine").unwrap(),
+            Endpoint::NamedPipe(PathBuf::from(r"\\.\pipe\docker_engine"))
+        );
+    }
+}
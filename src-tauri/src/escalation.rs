@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+/// Longest any single step (or the port-close wait) may block a disconnect.
+const MAX_STEP_TIMEOUT_MS: u64 = 120_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Signal {
+    Int,
+    Term,
+    Hup,
+    Quit,
+    Kill,
+}
+
+impl Signal {
+    pub fn name(self) -> &'static str {
+        match self {
+            Signal::Int => "INT",
+            Signal::Term => "TERM",
+            Signal::Hup => "HUP",
+            Signal::Quit => "QUIT",
+            Signal::Kill => "KILL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscalationStep {
+    pub signal: Signal,
+    /// How long to wait for the processes to exit before the next step.
+    pub timeout_ms: u64,
+}
+
+/// The signals a disconnect sends, in order, and how long it then waits for
+/// the port to be released.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscalationPolicy {
+    pub steps: Vec<EscalationStep>,
+    #[serde(default = "default_port_close_timeout_ms")]
+    pub port_close_timeout_ms: u64,
+}
+
+fn default_port_close_timeout_ms() -> u64 {
+    1_500
+}
+
+impl Default for EscalationPolicy {
+    fn default() -> Self {
+        Self::new(&[(Signal::Term, 800), (Signal::Kill, 800)])
+    }
+}
+
+impl EscalationPolicy {
+    fn new(steps: &[(Signal, u64)]) -> Self {
+        Self {
+            steps: steps
+                .iter()
+                .map(|&(signal, timeout_ms)| EscalationStep { signal, timeout_ms })
+                .collect(),
+            port_close_timeout_ms: default_port_close_timeout_ms(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("escalation policy needs at least one signal".to_string());
+        }
+        let too_long = self
+            .steps
+            .iter()
+            .map(|step| step.timeout_ms)
+            .chain([self.port_close_timeout_ms])
+            .any(|timeout_ms| timeout_ms > MAX_STEP_TIMEOUT_MS);
+        if too_long {
+            return Err(format!(
+                "escalation timeouts are limited to {} s",
+                MAX_STEP_TIMEOUT_MS / 1_000
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Applies `policy` to processes whose name equals `pattern` or whose command
+/// line contains it (case-insensitive). Built-in rules only compare names.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscalationRule {
+    pub pattern: String,
+    pub policy: EscalationPolicy,
+}
+
+impl EscalationRule {
+    fn matches(&self, process: &ProcessDescription) -> bool {
+        let pattern = self.pattern.trim().to_lowercase();
+        !pattern.is_empty()
+            && (self.matches_program(process)
+                || process.command_line.to_lowercase().contains(&pattern))
+    }
+
+    /// Whether the process name or `argv[0]`'s file name is `pattern`, so
+    /// `java` doesn't catch `node ~/code/javascript/server.js`.
+    fn matches_program(&self, process: &ProcessDescription) -> bool {
+        let pattern = self.pattern.trim();
+        !pattern.is_empty()
+            && [&process.name, &process.program].into_iter().any(|name| {
+                let name = name.strip_suffix(".exe").unwrap_or(name);
+                name.eq_ignore_ascii_case(pattern)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProcessDescription {
+    pub name: String,
+    /// The file name of `argv[0]`, which outlives the 15-character process
+    /// name Linux keeps.
+    pub program: String,
+    pub command_line: String,
+}
+
+/// Services that need longer than the default to shut down cleanly.
+fn builtin_rules() -> Vec<EscalationRule> {
+    let rule = |pattern: &str, steps: &[(Signal, u64)]| EscalationRule {
+        pattern: pattern.to_string(),
+        policy: EscalationPolicy::new(steps),
+    };
+    let slow = [(Signal::Term, 10_000), (Signal::Kill, 800)];
+    vec![
+        // SIGTERM is postgres's "smart" shutdown, which waits for every client.
+        rule(
+            "postgres",
+            &[
+                (Signal::Int, 10_000),
+                (Signal::Quit, 2_000),
+                (Signal::Kill, 800),
+            ],
+        ),
+        rule("mysqld", &slow),
+        rule("mariadbd", &slow),
+        rule("mongod", &slow),
+        rule("redis-server", &slow),
+        rule("java", &slow),
+    ]
+}
+
+/// Picks the policy for a disconnect: the one sent with the request, else the
+/// first user rule matching any target process, else the first built-in rule,
+/// else the default TERM/KILL escalation.
+pub fn resolve_policy(
+    requested: Option<EscalationPolicy>,
+    rules: &[EscalationRule],
+    processes: &[ProcessDescription],
+) -> EscalationPolicy {
+    if let Some(policy) = requested {
+        return policy;
+    }
+    let matching = |rules: &[EscalationRule],
+                    matches: fn(&EscalationRule, &ProcessDescription) -> bool| {
+        rules
+            .iter()
+            .find(|rule| processes.iter().any(|process| matches(rule, process)))
+            .map(|rule| rule.policy.clone())
+    };
+    matching(rules, EscalationRule::matches)
+        .or_else(|| matching(&builtin_rules(), EscalationRule::matches_program))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(name: &str, command_line: &str) -> ProcessDescription {
+        let program = command_line.split(' ').next().unwrap_or_default();
+        ProcessDescription {
+            name: name.to_string(),
+            program: program.rsplit('/').next().unwrap_or_default().to_string(),
+            command_line: command_line.to_string(),
+        }
+    }
+
+    #[test]
+    fn user_rules_win_over_builtin_rules_and_requests_win_over_both() {
+        let postgres = [process(
+            "postgres",
+            "/usr/lib/postgresql/16/bin/postgres -D /data",
+        )];
+        let user_rules = [EscalationRule {
+            pattern: "POSTGRESQL/16".to_string(),
+            policy: EscalationPolicy::new(&[(Signal::Term, 30_000)]),
+        }];
+
+        assert_eq!(
+            resolve_policy(None, &[], &postgres).steps[0].signal,
+            Signal::Int
+        );
+        assert_eq!(
+            resolve_policy(None, &user_rules, &postgres),
+            user_rules[0].policy
+        );
+        assert_eq!(
+            resolve_policy(Some(EscalationPolicy::default()), &user_rules, &postgres),
+            EscalationPolicy::default()
+        );
+        assert_eq!(
+            resolve_policy(None, &[], &[process("node", "node server.js")]),
+            EscalationPolicy::default()
+        );
+    }
+
+    #[test]
+    fn builtin_rules_only_match_the_program_name() {
+        let resolved =
+            |name, command_line| resolve_policy(None, &[], &[process(name, command_line)]);
+
+        assert_eq!(
+            resolved("node", "node /home/me/code/javascript/server.js"),
+            EscalationPolicy::default()
+        );
+        assert_eq!(
+            resolved("node", "node app.js --db postgres://localhost/shop"),
+            EscalationPolicy::default()
+        );
+        assert_eq!(
+            resolved("java", "/usr/bin/java -jar app.jar").steps[0].timeout_ms,
+            10_000
+        );
+        assert_eq!(
+            resolved("java.exe", r"C:\jdk\bin\java.exe -jar app.jar").steps[0].timeout_ms,
+            10_000
+        );
+        // A user rule still matches anywhere in the command line.
+        let user_rules = [EscalationRule {
+            pattern: "javascript".to_string(),
+            policy: EscalationPolicy::new(&[(Signal::Int, 2_000)]),
+        }];
+        assert_eq!(
+            resolve_policy(
+                None,
+                &user_rules,
+                &[process("node", "node /home/me/code/javascript/server.js")]
+            ),
+            user_rules[0].policy
+        );
+    }
+
+    #[test]
+    fn deserializes_policy_with_default_port_close_timeout() {
+        let policy: EscalationPolicy =
+            serde_json::from_str(r#"{"steps":[{"signal":"INT","timeout_ms":3000}]}"#).unwrap();
+
+        assert_eq!(policy, EscalationPolicy::new(&[(Signal::Int, 3_000)]));
+        assert!(policy.validate().is_ok());
+        assert!(EscalationPolicy::new(&[]).validate().is_err());
+        assert!(EscalationPolicy::new(&[(Signal::Term, 600_000)])
+            .validate()
+            .is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{monitor::ListenerEvent, ListenerInfo, Protocol};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::Serialize;
+use std::{path::Path, sync::Mutex};
+
+/// How long closed sessions are kept unless the user changes it. 0 keeps
+/// them forever.
+const DEFAULT_RETENTION_DAYS: u32 = 30;
+const PRUNE_EVERY_SECS: u64 = 3_600;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS listener_sessions (
+    id INTEGER PRIMARY KEY,
+    port INTEGER NOT NULL,
+    protocol TEXT NOT NULL,
+    pid INTEGER,
+    process_start INTEGER,
+    process_name TEXT,
+    command_line TEXT,
+    container_id TEXT,
+    container_name TEXT,
+    first_seen INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL,
+    disappeared_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS listener_sessions_port ON listener_sessions (port, first_seen);
+CREATE INDEX IF NOT EXISTS listener_sessions_open ON listener_sessions (disappeared_at);
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+/// One process holding one port for an unbroken stretch of time, as observed
+/// by the monitor. Times are Unix seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListenerSession {
+    pub port: u16,
+    pub protocol: Protocol,
+    pub pid: Option<u32>,
+    pub process_name: Option<String>,
+    pub command_line: Option<String>,
+    pub container_name: Option<String>,
+    pub first_seen: u64,
+    /// `None` while it is still listening.
+    pub disappeared_at: Option<u64>,
+    pub lifetime_seconds: u64,
+}
+
+/// The persistent listener history, fed by the monitor.
+pub struct History {
+    db: Mutex<Db>,
+    /// Why the history is kept in memory instead of on disk.
+    unpersisted: Option<String>,
+}
+
+struct Db {
+    conn: Connection,
+    last_pruned: u64,
+}
+
+impl History {
+    pub fn open(path: &Path, now: u64) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?, now)
+    }
+
+    /// A history that is lost on exit, for when the data dir isn't writable.
+    pub fn in_memory(now: u64) -> Self {
+        Self::init(Connection::open_in_memory().expect("in-memory sqlite"), now)
+            .expect("in-memory history schema")
+    }
+
+    /// An in-memory history standing in for one that couldn't be opened.
+    pub fn unpersisted(reason: String, now: u64) -> Self {
+        Self {
+            unpersisted: Some(reason),
+            ..Self::in_memory(now)
+        }
+    }
+
+    /// Why the history will be lost on exit, if it will.
+    pub fn unpersisted_reason(&self) -> Option<&str> {
+        self.unpersisted.as_deref()
+    }
+
+    fn init(conn: Connection, now: u64) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        // Sessions still open were cut short by the previous run exiting; they
+        // ended, as far as we know, when they were last seen.
+        conn.execute(
+            "UPDATE listener_sessions SET disappeared_at = last_seen WHERE disappeared_at IS NULL",
+            [],
+        )?;
+        let mut db = Db {
+            conn,
+            last_pruned: 0,
+        };
+        db.prune(now)?;
+        Ok(Self {
+            db: Mutex::new(db),
+            unpersisted: None,
+        })
+    }
+
+    /// Applies one monitor scan: opens sessions for new listeners and
+    /// processes, closes those that went away, and marks the rest as seen.
+    pub fn record(&self, events: &[ListenerEvent], now: u64) -> rusqlite::Result<()> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.conn.transaction()?;
+        for event in events {
+            match event {
+                ListenerEvent::Added(listener) | ListenerEvent::Changed(listener) => {
+                    sync_listener(&tx, listener, now)?;
+                }
+                ListenerEvent::Removed(listener) => {
+                    tx.execute(
+                        "UPDATE listener_sessions SET disappeared_at = ?3
+                         WHERE port = ?1 AND protocol = ?2 AND disappeared_at IS NULL",
+                        params![listener.port, protocol_name(listener.protocol), now],
+                    )?;
+                }
+            }
+        }
+        tx.execute(
+            "UPDATE listener_sessions SET last_seen = ?1 WHERE disappeared_at IS NULL",
+            [now],
+        )?;
+        tx.commit()?;
+        if now >= db.last_pruned + PRUNE_EVERY_SECS {
+            db.prune(now)?;
+        }
+        Ok(())
+    }
+
+    /// Sessions that overlap `[from, to]`, optionally only on `port`, oldest
+    /// first.
+    pub fn sessions(
+        &self,
+        port: Option<u16>,
+        from: u64,
+        to: u64,
+        now: u64,
+    ) -> rusqlite::Result<Vec<ListenerSession>> {
+        let db = self.db.lock().unwrap();
+        let mut statement = db.conn.prepare(
+            "SELECT port, protocol, pid, process_name, command_line, container_name,
+                    first_seen, disappeared_at
+             FROM listener_sessions
+             WHERE (?1 IS NULL OR port = ?1)
+               AND first_seen <= ?3
+               AND COALESCE(disappeared_at, ?4) >= ?2
+             ORDER BY first_seen, port",
+        )?;
+        let rows = statement.query_map(params![port, from, to, now], |row| {
+            let first_seen: u64 = row.get(6)?;
+            let disappeared_at: Option<u64> = row.get(7)?;
+            Ok(ListenerSession {
+                port: row.get(0)?,
+                protocol: protocol_from_name(&row.get::<_, String>(1)?),
+                pid: row.get(2)?,
+                process_name: row.get(3)?,
+                command_line: row.get(4)?,
+                container_name: row.get(5)?,
+                first_seen,
+                disappeared_at,
+                lifetime_seconds: disappeared_at.unwrap_or(now).saturating_sub(first_seen),
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn retention_days(&self) -> rusqlite::Result<u32> {
+        self.db.lock().unwrap().retention_days()
+    }
+
+    pub fn set_retention_days(&self, days: u32, now: u64) -> rusqlite::Result<()> {
+        let mut db = self.db.lock().unwrap();
+        db.conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('retention_days', ?1)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [days.to_string()],
+        )?;
+        db.prune(now)
+    }
+}
+
+impl Db {
+    fn retention_days(&self) -> rusqlite::Result<u32> {
+        let stored: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'retention_days'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(stored
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_RETENTION_DAYS))
+    }
+
+    fn prune(&mut self, now: u64) -> rusqlite::Result<()> {
+        self.last_pruned = now;
+        let days = self.retention_days()?;
+        if days == 0 {
+            return Ok(());
+        }
+        let cutoff = now.saturating_sub(u64::from(days) * 86_400);
+        self.conn.execute(
+            "DELETE FROM listener_sessions WHERE disappeared_at IS NOT NULL AND disappeared_at < ?1",
+            [cutoff],
+        )?;
+        Ok(())
+    }
+}
+
+/// Makes the open sessions for `listener`'s port match its current processes.
+/// A listener without a known process (e.g. a Docker Desktop port) gets one
+/// session with no pid.
+fn sync_listener(tx: &Transaction, listener: &ListenerInfo, now: u64) -> rusqlite::Result<()> {
+    let protocol = protocol_name(listener.protocol);
+    let current: Vec<(Option<u32>, Option<u64>)> = if listener.processes.is_empty() {
+        vec![(None, None)]
+    } else {
+        listener
+            .processes
+            .iter()
+            .map(|process| (Some(process.pid), process.identity.start_time))
+            .collect()
+    };
+
+    let open: Vec<(i64, Option<u32>, Option<u64>)> = {
+        let mut statement = tx.prepare(
+            "SELECT id, pid, process_start FROM listener_sessions
+             WHERE port = ?1 AND protocol = ?2 AND disappeared_at IS NULL",
+        )?;
+        let rows = statement.query_map(params![listener.port, protocol], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    for (id, pid, start) in &open {
+        if !current.contains(&(*pid, *start)) {
+            tx.execute(
+                "UPDATE listener_sessions SET disappeared_at = ?2 WHERE id = ?1",
+                params![id, now],
+            )?;
+        }
+    }
+    for key in &current {
+        if open.iter().any(|(_, pid, start)| (*pid, *start) == *key) {
+            continue;
+        }
+        let process = listener
+            .processes
+            .iter()
+            .find(|process| Some(process.pid) == key.0);
+        tx.execute(
+            "INSERT INTO listener_sessions (port, protocol, pid, process_start, process_name,
+                 command_line, container_id, container_name, first_seen, last_seen)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+            params![
+                listener.port,
+                protocol,
+                key.0,
+                key.1,
+                process.and_then(|p| p.process_name.clone()),
+                process
+                    .filter(|p| !p.command_line.is_empty())
+                    .map(|p| p.command_line.join(" ")),
+                listener.container_id,
+                listener.container_name,
+                now,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn protocol_name(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::Tcp => "tcp",
+        Protocol::Udp => "udp",
+    }
+}
+
+fn protocol_from_name(name: &str) -> Protocol {
+    match name {
+        "udp" => Protocol::Udp,
+        _ => Protocol::Tcp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ListenerIdentity, ListenerProcess};
+    use std::net::IpAddr;
+
+    fn listener(port: u16, pids: &[u32]) -> ListenerInfo {
+        let mut listener =
+            ListenerInfo::new(port, Protocol::Tcp, "127.0.0.1".parse::<IpAddr>().unwrap());
+        for &pid in pids {
+            listener.add_process(ListenerProcess {
+                pid,
+                process_name: Some(format!("proc{pid}")),
+                identity: ListenerIdentity {
+                    pid,
+                    start_time: Some(1_000),
+                    socket_inode: None,
+                },
+                ..Default::default()
+            });
+        }
+        listener
+    }
+
+    fn held(sessions: &[ListenerSession]) -> Vec<(u16, Option<u32>, u64, Option<u64>)> {
+        sessions
+            .iter()
+            .map(|s| (s.port, s.pid, s.first_seen, s.disappeared_at))
+            .collect()
+    }
+
+    #[test]
+    fn records_who_held_a_port_and_for_how_long() {
+        let history = History::in_memory(0);
+        history
+            .record(&[ListenerEvent::Added(listener(8080, &[10]))], 100)
+            .unwrap();
+        history
+            .record(&[ListenerEvent::Added(listener(3000, &[20]))], 150)
+            .unwrap();
+        // Replaced by another process on the same port.
+        history
+            .record(&[ListenerEvent::Changed(listener(8080, &[11]))], 200)
+            .unwrap();
+        history
+            .record(&[ListenerEvent::Removed(listener(8080, &[11]))], 300)
+            .unwrap();
+
+        assert_eq!(
+            held(&history.sessions(Some(8080), 120, 180, 400).unwrap()),
+            [(8080, Some(10), 100, Some(200))]
+        );
+        let all = history.sessions(None, 0, 400, 400).unwrap();
+        assert_eq!(
+            held(&all),
+            [
+                (8080, Some(10), 100, Some(200)),
+                (3000, Some(20), 150, None),
+                (8080, Some(11), 200, Some(300)),
+            ]
+        );
+        assert_eq!(all[1].lifetime_seconds, 250);
+    }
+
+    #[test]
+    fn prunes_sessions_older_than_the_retention() {
+        let history = History::in_memory(0);
+        history
+            .record(&[ListenerEvent::Added(listener(8080, &[10]))], 100)
+            .unwrap();
+        history
+            .record(&[ListenerEvent::Removed(listener(8080, &[10]))], 200)
+            .unwrap();
+        assert_eq!(history.retention_days().unwrap(), DEFAULT_RETENTION_DAYS);
+
+        history.set_retention_days(1, 200 + 86_400).unwrap();
+        assert_eq!(history.sessions(None, 0, 1_000_000, 0).unwrap().len(), 1);
+        history.set_retention_days(1, 201 + 86_400).unwrap();
+        assert!(history.sessions(None, 0, 1_000_000, 0).unwrap().is_empty());
+    }
+}
@@ -1,23 +1,66 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+#[cfg(target_os = "linux")]
+mod cgroups;
+mod containers;
+mod docker;
+mod escalation;
+mod history;
+mod monitor;
+#[cfg(target_os = "linux")]
+mod netlink;
+mod processes;
+mod project;
+mod protection;
+mod proxies;
+mod relaunch;
+mod runtime;
+#[cfg(not(windows))]
+mod signals;
+
 use tauri::{
+    ipc::{CommandArg, CommandItem, InvokeError},
     menu::{Menu, MenuItem},
     tray::TrayIconBuilder,
-    Manager, WindowEvent,
+    Manager, State, WindowEvent,
 };
 
 #[cfg(target_os = "macos")]
 use tauri::ActivationPolicy;
 
-use netstat2::{get_sockets_info, AddressFamilyFlags, ProtocolFlags, ProtocolSocketInfo, TcpState};
+use containers::{
+    container_processes, containers_to_stop, group_by_host_port, process_container,
+    proxied_container, running_containers, PublishedContainer, UnavailableRuntime,
+};
+use escalation::{resolve_policy, EscalationPolicy, EscalationRule, ProcessDescription};
+use history::{History, ListenerSession};
+use monitor::{ListenTimes, Monitor, MonitorConfig};
+use netstat2::{
+    get_sockets_info, AddressFamilyFlags, ProtocolFlags, ProtocolSocketInfo, SocketInfo, TcpState,
+};
+use processes::ProcessCache;
+use project::{detect_project, ProjectInfo};
+use protection::{
+    protection_reason, user_protection_reason, ProcessFacts, ProtectedList, ProtectedMatcher,
+};
+use proxies::{proxy_of, Proxy};
+use relaunch::ProcessSnapshot;
+use runtime::{ComposeInfo, Container, ContainerRuntime, PortBinding, RuntimeKind};
 use serde::{Deserialize, Serialize};
+#[cfg(not(windows))]
+use signals::SignalError;
 use std::{
-    collections::HashMap,
-    process::{Command, Output},
+    collections::{BTreeMap, HashMap, HashSet},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use sysinfo::{Pid, ProcessesToUpdate, System};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, Uid, UpdateKind, Users};
 
 #[derive(Debug, Clone, Deserialize)]
 struct PortRange {
@@ -25,20 +68,411 @@ struct PortRange {
     end: u16,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    fn flags(self) -> ProtocolFlags {
+        match self {
+            Protocol::Tcp => ProtocolFlags::TCP,
+            Protocol::Udp => ProtocolFlags::UDP,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn label(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    fn of(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => AddressFamily::Ipv4,
+            IpAddr::V6(_) => AddressFamily::Ipv6,
+        }
+    }
+}
+
+/// Who can reach a socket, judged from the address it is bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Exposure {
+    Loopback,
+    Lan,
+    AllInterfaces,
+}
+
+impl Exposure {
+    fn of(addr: IpAddr) -> Self {
+        let addr = addr.to_canonical();
+        if addr.is_unspecified() {
+            Exposure::AllInterfaces
+        } else if addr.is_loopback() {
+            Exposure::Loopback
+        } else {
+            Exposure::Lan
+        }
+    }
+}
+
+/// Everything bound to one port/protocol: a dual-stack server shows up as one
+/// listener with an IPv4 and an IPv6 socket, and a SO_REUSEPORT worker pool as
+/// one listener with several processes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
 struct ListenerInfo {
     port: u16,
+    protocol: Protocol,
+    /// The widest exposure of any of the bound sockets.
+    exposure: Exposure,
+    sockets: Vec<BoundSocket>,
+    processes: Vec<ListenerProcess>,
+    connections: ConnectionSummary,
+    container_id: Option<String>,
+    container_name: Option<String>,
+    container_runtime: Option<RuntimeKind>,
+    compose: Option<ComposeInfo>,
+    /// Every container binding of this host port; the `container_*` fields
+    /// describe the first. Different containers may publish the same port on
+    /// different host IPs.
+    container_bindings: Vec<ContainerBinding>,
+}
+
+/// A container port published on a listener's host port.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ContainerBinding {
+    container_id: String,
+    container_name: String,
+    runtime: RuntimeKind,
+    #[serde(flatten)]
+    binding: PortBinding,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct BoundSocket {
+    local_addr: IpAddr,
+    address_family: AddressFamily,
+    exposure: Exposure,
+    /// Summed over the sockets bound to this address; only sock_diag
+    /// reports it.
+    queue: Option<SocketQueue>,
+    /// Who owns the socket, known even when its processes can't be read.
+    user: Option<String>,
+}
+
+/// What is waiting on a socket. For a TCP listener `received` is the accept
+/// backlog and `sent` its limit; for UDP both are bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+struct SocketQueue {
+    received: u32,
+    sent: u32,
+}
+
+/// A socket from `read_sockets`, with its queue when sock_diag reported it.
+struct Socket {
+    info: SocketInfo,
+    queue: Option<SocketQueue>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+struct ListenerProcess {
     pid: u32,
     process_name: Option<String>,
+    /// Since the process started, which can be long before it began listening.
     started_seconds_ago: Option<u64>,
-    container_id: Option<String>,
-    container_name: Option<String>,
+    /// Since Port-o-Potty first saw this process listening on the port.
+    listening_seconds_ago: Option<u64>,
+    /// It was already listening when its port was first scanned, at launch or
+    /// when a range was added, so `listening_seconds_ago` is a lower bound.
+    listening_before_launch: bool,
+    command_line: Vec<String>,
+    cwd: Option<String>,
+    exe_path: Option<String>,
+    user: Option<String>,
+    parent_pid: Option<u32>,
+    project: Option<ProjectInfo>,
+    /// Set when this is a userland proxy forwarding the port to a container.
+    proxy: Option<Proxy>,
+    identity: ListenerIdentity,
 }
 
-#[derive(Debug, Clone)]
-struct DockerPublishedContainer {
-    id: String,
-    name: String,
+/// Pins a listening process so a disconnect issued from a stale scan can tell
+/// it apart from an unrelated process that has since reused its pid. The UI
+/// sees an opaque `pid:start_time:inode` token and hands it back unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+struct ListenerIdentity {
+    pid: u32,
+    /// Process start, in seconds since the epoch.
+    start_time: Option<u64>,
+    /// Inode of the listening socket; only known on Linux.
+    socket_inode: Option<u64>,
+}
+
+impl From<ListenerIdentity> for String {
+    fn from(identity: ListenerIdentity) -> Self {
+        let field = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+        format!(
+            "{}:{}:{}",
+            identity.pid,
+            field(identity.start_time),
+            field(identity.socket_inode)
+        )
+    }
+}
+
+impl TryFrom<String> for ListenerIdentity {
+    type Error = String;
+
+    fn try_from(token: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid listener identity: {token}");
+        let field = |value: &str| -> Result<Option<u64>, String> {
+            if value.is_empty() {
+                Ok(None)
+            } else {
+                value.parse().map(Some).map_err(|_| invalid())
+            }
+        };
+        let mut parts = token.split(':');
+        let (Some(pid), Some(start_time), Some(socket_inode), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        Ok(ListenerIdentity {
+            pid: pid.parse().map_err(|_| invalid())?,
+            start_time: field(start_time)?,
+            socket_inode: field(socket_inode)?,
+        })
+    }
+}
+
+impl ListenerIdentity {
+    /// Whether the process that now has this pid, started at
+    /// `current_start_time`, is still the one bound to the listener's port.
+    /// `bound` lists the (pid, socket inode) pairs currently on that port.
+    fn matches(&self, current_start_time: Option<u64>, bound: &[(u32, Option<u64>)]) -> bool {
+        let same_process = match (self.start_time, current_start_time) {
+            (Some(then), Some(now)) => then == now,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        same_process
+            && bound.iter().any(|&(pid, inode)| {
+                pid == self.pid && (self.socket_inode.is_none() || inode == self.socket_inode)
+            })
+    }
+}
+
+/// Connections accepted on a TCP listener's port. Counts cover every
+/// connection; `peers` is capped at `MAX_PEERS` entries.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+struct ConnectionSummary {
+    established: u32,
+    time_wait: u32,
+    close_wait: u32,
+    peers: Vec<ConnectionPeer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ConnectionPeer {
+    remote_addr: IpAddr,
+    remote_port: u16,
+    state: ConnectionState,
+    /// The process on the other end, when the peer is on this machine.
+    pid: Option<u32>,
+    process_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ConnectionState {
+    Established,
+    TimeWait,
+    CloseWait,
+}
+
+impl ConnectionState {
+    fn from_tcp(state: TcpState) -> Option<Self> {
+        match state {
+            TcpState::Established => Some(ConnectionState::Established),
+            TcpState::TimeWait => Some(ConnectionState::TimeWait),
+            TcpState::CloseWait => Some(ConnectionState::CloseWait),
+            _ => None,
+        }
+    }
+}
+
+const MAX_PEERS: usize = 50;
+
+impl ConnectionSummary {
+    fn record(&mut self, peer: ConnectionPeer) {
+        match peer.state {
+            ConnectionState::Established => self.established += 1,
+            ConnectionState::TimeWait => self.time_wait += 1,
+            ConnectionState::CloseWait => self.close_wait += 1,
+        }
+        if self.peers.len() < MAX_PEERS {
+            self.peers.push(peer);
+        }
+    }
+}
+
+impl BoundSocket {
+    /// Adds the queue of one more socket bound to this address, as each
+    /// SO_REUSEPORT worker has its own.
+    fn add_queue(&mut self, queue: Option<SocketQueue>) {
+        let Some(queue) = queue else {
+            return;
+        };
+        let total = self.queue.get_or_insert_with(SocketQueue::default);
+        total.received = total.received.saturating_add(queue.received);
+        total.sent = total.sent.saturating_add(queue.sent);
+    }
+}
+
+impl ListenerInfo {
+    fn new(port: u16, protocol: Protocol, local_addr: IpAddr) -> Self {
+        Self {
+            port,
+            protocol,
+            exposure: Exposure::of(local_addr),
+            sockets: Vec::new(),
+            processes: Vec::new(),
+            connections: ConnectionSummary::default(),
+            container_id: None,
+            container_name: None,
+            container_runtime: None,
+            compose: None,
+            container_bindings: Vec::new(),
+        }
+    }
+
+    fn add_socket(&mut self, local_addr: IpAddr) -> &mut BoundSocket {
+        if !self.sockets.iter().any(|s| s.local_addr == local_addr) {
+            let exposure = Exposure::of(local_addr);
+            self.exposure = self.exposure.max(exposure);
+            self.sockets.push(BoundSocket {
+                local_addr,
+                address_family: AddressFamily::of(local_addr),
+                exposure,
+                queue: None,
+                user: None,
+            });
+            self.sockets
+                .sort_by_key(|s| (s.address_family, s.local_addr));
+        }
+        self.sockets
+            .iter_mut()
+            .find(|s| s.local_addr == local_addr)
+            .expect("socket was just added")
+    }
+
+    fn attach_containers(&mut self, published: &[(PublishedContainer, PortBinding)]) {
+        let Some((first, _)) = published.first() else {
+            return;
+        };
+        self.container_id = Some(first.id.clone());
+        self.container_name = Some(first.name.clone());
+        self.container_runtime = Some(first.runtime);
+        self.compose = first.compose.clone();
+        self.container_bindings = published
+            .iter()
+            .map(|(container, binding)| ContainerBinding {
+                container_id: container.id.clone(),
+                container_name: container.name.clone(),
+                runtime: container.runtime,
+                binding: binding.clone(),
+            })
+            .collect();
+    }
+
+    /// Attributes the listener to the container its process runs in (a
+    /// host-network container publishes nothing) or forwards to.
+    fn attach_process_container(&mut self, owner: PublishedContainer) {
+        self.container_id = Some(owner.id);
+        self.container_name = Some(owner.name);
+        self.container_runtime = Some(owner.runtime);
+        self.compose = owner.compose;
+    }
+
+    fn has_process(&self, pid: u32) -> bool {
+        self.processes.iter().any(|p| p.pid == pid)
+    }
+
+    fn add_process(&mut self, process: ListenerProcess) {
+        if self.has_process(process.pid) {
+            return;
+        }
+        self.processes.push(process);
+        self.processes.sort_by_key(|p| p.pid);
+    }
+}
+
+/// Points the background monitor at the UI's port ranges and rescan interval.
+#[tauri::command]
+fn configure_monitor(config: MonitorConfig, monitor: State<'_, Monitor>) {
+    monitor.configure(config);
+}
+
+/// Every session overlapping `[from, to]` (Unix seconds), optionally only on
+/// `port`: "who held 8080 yesterday afternoon".
+#[tauri::command]
+fn listener_history(
+    port: Option<u16>,
+    from: u64,
+    to: u64,
+    history: State<'_, History>,
+) -> Result<Vec<ListenerSession>, String> {
+    history
+        .sessions(port, from, to, unix_now())
+        .map_err(|e| e.to_string())
+}
+
+/// Every listener seen in the last `hours` (default 24) and how long it lived.
+#[tauri::command]
+fn listener_lifetimes(
+    hours: Option<u64>,
+    history: State<'_, History>,
+) -> Result<Vec<ListenerSession>, String> {
+    let now = unix_now();
+    let from = now.saturating_sub(hours.unwrap_or(24).saturating_mul(3_600));
+    history
+        .sessions(None, from, now, now)
+        .map_err(|e| e.to_string())
+}
+
+/// Why listener history won't outlive this run, if it won't.
+#[tauri::command]
+fn history_unpersisted_reason(history: State<'_, History>) -> Option<String> {
+    history.unpersisted_reason().map(str::to_string)
+}
+
+#[tauri::command]
+fn history_retention(history: State<'_, History>) -> Result<u32, String> {
+    history.retention_days().map_err(|e| e.to_string())
+}
+
+/// Sets how many days closed sessions are kept; 0 keeps them forever.
+#[tauri::command]
+fn set_history_retention(days: u32, history: State<'_, History>) -> Result<(), String> {
+    history
+        .set_retention_days(days, unix_now())
+        .map_err(|e| e.to_string())
 }
 
 fn in_any_range(port: u16, ranges: &[PortRange]) -> bool {
@@ -52,309 +486,1552 @@ fn in_any_range(port: u16, ranges: &[PortRange]) -> bool {
     })
 }
 
+/// The listeners in range, and how long it took to find them.
+#[derive(Debug, Clone, Serialize)]
+struct ScanResult {
+    listeners: Vec<ListenerInfo>,
+    scan_duration_ms: f64,
+    /// Runtimes that are set up but couldn't be reached or didn't answer, so
+    /// their containers are missing from `listeners`.
+    unavailable_runtimes: Vec<UnavailableRuntime>,
+    /// Why sockets are read through netstat2 instead of sock_diag, if they are.
+    socket_fallback: Option<String>,
+}
+
+/// How long a container runtime gets to answer before the call is abandoned,
+/// as set by the UI through `set_runtime_timeout`.
+struct RuntimeTimeout(AtomicU64);
+
+/// A runtime call can't be given less than this.
+const MIN_RUNTIME_TIMEOUT_MS: u64 = 250;
+
+impl Default for RuntimeTimeout {
+    fn default() -> Self {
+        Self(AtomicU64::new(runtime::DEFAULT_TIMEOUT.as_millis() as u64))
+    }
+}
+
+impl RuntimeTimeout {
+    fn get(&self) -> Duration {
+        Duration::from_millis(self.0.load(Ordering::Relaxed))
+    }
+}
+
 #[tauri::command]
-fn scan_ports(ranges: Vec<PortRange>) -> Result<Vec<ListenerInfo>, String> {
+fn set_runtime_timeout(timeout_ms: u64, timeout: State<'_, RuntimeTimeout>) {
+    timeout
+        .0
+        .store(timeout_ms.max(MIN_RUNTIME_TIMEOUT_MS), Ordering::Relaxed);
+}
+
+#[tauri::command(async)]
+fn scan_ports(
+    ranges: Vec<PortRange>,
+    listen_times: State<'_, ListenTimes>,
+    processes: State<'_, ProcessCache>,
+    runtime_timeout: State<'_, RuntimeTimeout>,
+) -> Result<ScanResult, String> {
+    let started = Instant::now();
     if ranges.is_empty() {
-        return Ok(vec![]);
+        return Ok(ScanResult {
+            listeners: vec![],
+            scan_duration_ms: 0.0,
+            unavailable_runtimes: vec![],
+            socket_fallback: sock_diag_error(),
+        });
+    }
+
+    let sockets = read_sockets(
+        &[Protocol::Tcp, Protocol::Udp],
+        SocketStates::WithConnections,
+    )?;
+    let (running, unavailable_runtimes) = running_containers(runtime_timeout.get());
+    let container_ports = group_by_host_port(running.clone());
+
+    let listeners =
+        processes.with_processes(&pids_to_describe(&sockets, &ranges), |system, users| {
+            let mut projects: HashMap<PathBuf, Option<ProjectInfo>> = HashMap::new();
+            let mut listeners: BTreeMap<(u16, Protocol), ListenerInfo> = BTreeMap::new();
+            for socket in &sockets {
+                let Some((protocol, local_addr, port)) =
+                    bound_port(&socket.info.protocol_socket_info)
+                else {
+                    continue;
+                };
+                if !in_any_range(port, &ranges) {
+                    continue;
+                }
+
+                let listener = listeners.entry((port, protocol)).or_insert_with(|| {
+                    let mut listener = ListenerInfo::new(port, protocol, local_addr);
+                    if let Some(published) = container_ports.get(&(port, protocol)) {
+                        listener.attach_containers(published);
+                    }
+                    listener
+                });
+                let bound = listener.add_socket(local_addr);
+                bound.add_queue(socket.queue);
+                if bound.user.is_none() {
+                    bound.user = socket_owner(&socket.info)
+                        .and_then(|uid| users.get_user_by_id(&uid))
+                        .map(|user| user.name().to_string());
+                }
+
+                for &pid in &socket.info.associated_pids {
+                    if listener.has_process(pid) {
+                        continue;
+                    }
+                    let mut process = describe_process(system, users, &mut projects, pid);
+                    process.identity.socket_inode = socket_inode(&socket.info);
+                    let proxied = process
+                        .proxy
+                        .as_ref()
+                        .and_then(|proxy| proxied_container(proxy, &running));
+                    listener.add_process(process);
+                    if let Some(owner) = proxied {
+                        // Among several containers on the port, the one this
+                        // proxy forwards to is the owner.
+                        listener.attach_process_container(owner);
+                    } else if listener.container_id.is_none() {
+                        if let Some(owner) = process_container(pid, &running) {
+                            listener.attach_process_container(owner);
+                        }
+                    }
+                }
+            }
+
+            attach_connections(&mut listeners, &sockets, system);
+            listeners
+        });
+
+    let mut listeners: Vec<ListenerInfo> = listeners.into_values().collect();
+    listen_times.observe(&mut listeners, &ranges, unix_now());
+    Ok(ScanResult {
+        listeners,
+        scan_duration_ms: started.elapsed().as_secs_f64() * 1_000.0,
+        unavailable_runtimes,
+        socket_fallback: sock_diag_error(),
+    })
+}
+
+/// The pids a scan has to describe: owners of sockets bound in `ranges`, and
+/// the local processes connected to them.
+fn pids_to_describe(sockets: &[Socket], ranges: &[PortRange]) -> HashSet<u32> {
+    let mut pids = HashSet::new();
+    let mut peers = HashSet::new();
+    for Socket { info: socket, .. } in sockets {
+        if let Some((_, _, port)) = bound_port(&socket.protocol_socket_info) {
+            if in_any_range(port, ranges) {
+                pids.extend(&socket.associated_pids);
+            }
+        } else if let ProtocolSocketInfo::Tcp(tcp) = &socket.protocol_socket_info {
+            if in_any_range(tcp.local_port, ranges)
+                && ConnectionState::from_tcp(tcp.state).is_some()
+            {
+                peers.insert((tcp.remote_addr.to_canonical(), tcp.remote_port));
+            }
+        }
+    }
+    for Socket { info: socket, .. } in sockets {
+        if let ProtocolSocketInfo::Tcp(tcp) = &socket.protocol_socket_info {
+            if peers.contains(&(tcp.local_addr.to_canonical(), tcp.local_port)) {
+                pids.extend(socket.associated_pids.first());
+            }
+        }
     }
+    pids
+}
 
-    let af_flags = AddressFamilyFlags::IPV4 | AddressFamilyFlags::IPV6;
-    let proto_flags = ProtocolFlags::TCP;
-    let sockets = get_sockets_info(af_flags, proto_flags).map_err(|e| e.to_string())?;
+fn describe_process(
+    system: &System,
+    users: &Users,
+    projects: &mut HashMap<PathBuf, Option<ProjectInfo>>,
+    pid: u32,
+) -> ListenerProcess {
+    let Some(proc) = system.process(Pid::from_u32(pid)) else {
+        return ListenerProcess {
+            pid,
+            identity: ListenerIdentity {
+                pid,
+                ..ListenerIdentity::default()
+            },
+            ..ListenerProcess::default()
+        };
+    };
+    let process_name = proc.name().to_string_lossy().to_string();
+    let command_line: Vec<String> = proc
+        .cmd()
+        .iter()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+    ListenerProcess {
+        pid,
+        proxy: proxy_of(&process_name, &command_line),
+        process_name: Some(process_name),
+        started_seconds_ago: Some(proc.run_time()),
+        command_line,
+        cwd: proc.cwd().map(|path| path.to_string_lossy().to_string()),
+        exe_path: proc.exe().map(|path| path.to_string_lossy().to_string()),
+        user: proc
+            .user_id()
+            .and_then(|uid| users.get_user_by_id(uid))
+            .map(|user| user.name().to_string()),
+        parent_pid: proc.parent().map(|parent| parent.as_u32()),
+        project: proc.cwd().and_then(|cwd| {
+            projects
+                .entry(cwd.to_path_buf())
+                .or_insert_with(|| detect_project(cwd))
+                .clone()
+        }),
+        identity: ListenerIdentity {
+            pid,
+            start_time: Some(proc.start_time()),
+            socket_inode: None,
+        },
+        // Filled in by `ListenTimes::observe` once the listener is grouped.
+        ..ListenerProcess::default()
+    }
+}
 
-    let mut system = System::new_all();
-    system.refresh_processes(ProcessesToUpdate::All, true);
-    let docker_ports = docker_published_containers_by_port();
+/// Counts the connections accepted on each TCP listener and resolves local
+/// peers (a browser tab, a test runner) to the process holding the other end.
+fn attach_connections(
+    listeners: &mut BTreeMap<(u16, Protocol), ListenerInfo>,
+    sockets: &[Socket],
+    system: &System,
+) {
+    let mut local_endpoints: HashMap<(IpAddr, u16), u32> = HashMap::new();
+    for Socket { info: socket, .. } in sockets {
+        if let ProtocolSocketInfo::Tcp(tcp) = &socket.protocol_socket_info {
+            if let Some(&pid) = socket.associated_pids.first() {
+                local_endpoints.insert((tcp.local_addr.to_canonical(), tcp.local_port), pid);
+            }
+        }
+    }
 
-    let mut out: Vec<ListenerInfo> = Vec::new();
-    for socket in sockets {
-        let (port, is_listen) = match socket.protocol_socket_info {
-            ProtocolSocketInfo::Tcp(tcp) => (tcp.local_port, tcp.state == TcpState::Listen),
-            _ => continue,
+    for Socket { info: socket, .. } in sockets {
+        let ProtocolSocketInfo::Tcp(tcp) = &socket.protocol_socket_info else {
+            continue;
         };
-        if !is_listen {
+        let Some(state) = ConnectionState::from_tcp(tcp.state) else {
             continue;
-        }
-        if !in_any_range(port, &ranges) {
+        };
+        let Some(listener) = listeners.get_mut(&(tcp.local_port, Protocol::Tcp)) else {
             continue;
+        };
+
+        let pid = local_endpoints
+            .get(&(tcp.remote_addr.to_canonical(), tcp.remote_port))
+            .copied();
+        let process_name = pid
+            .and_then(|pid| system.process(Pid::from_u32(pid)))
+            .map(|p| p.name().to_string_lossy().to_string());
+        listener.connections.record(ConnectionPeer {
+            remote_addr: tcp.remote_addr,
+            remote_port: tcp.remote_port,
+            state,
+            pid,
+            process_name,
+        });
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn socket_inode(socket: &SocketInfo) -> Option<u64> {
+    Some(u64::from(socket.inode))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn socket_inode(_socket: &SocketInfo) -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn socket_owner(socket: &SocketInfo) -> Option<Uid> {
+    Uid::try_from(socket.uid as usize).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn socket_owner(_socket: &SocketInfo) -> Option<Uid> {
+    None
+}
+
+/// Which TCP sockets a caller needs. UDP sockets have no listen state, so
+/// all of them are always read.
+#[derive(Debug, Clone, Copy)]
+enum SocketStates {
+    Listening,
+    /// Listening plus established, TIME_WAIT and CLOSE_WAIT connections.
+    WithConnections,
+}
+
+/// Why sock_diag failed for TCP and for UDP, once it has. A protocol isn't
+/// tried again after that, but the other keeps using it: a kernel can lack
+/// UDP diag alone.
+#[cfg(target_os = "linux")]
+static SOCK_DIAG_ERRORS: [std::sync::OnceLock<String>; 2] =
+    [std::sync::OnceLock::new(), std::sync::OnceLock::new()];
+
+#[cfg(target_os = "linux")]
+fn sock_diag_errors(protocol: Protocol) -> &'static std::sync::OnceLock<String> {
+    &SOCK_DIAG_ERRORS[match protocol {
+        Protocol::Tcp => 0,
+        Protocol::Udp => 1,
+    }]
+}
+
+#[cfg(target_os = "linux")]
+fn sock_diag_error() -> Option<String> {
+    let failed: Vec<String> = [Protocol::Tcp, Protocol::Udp]
+        .into_iter()
+        .filter_map(|protocol| {
+            let error = sock_diag_errors(protocol).get()?;
+            Some(format!("{}: {error}", protocol.label()))
+        })
+        .collect();
+    (!failed.is_empty()).then(|| failed.join(", "))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sock_diag_error() -> Option<String> {
+    None
+}
+
+/// Runs `ask` over sock_diag for `protocol` unless that has already failed,
+/// remembering the first failure so later calls go straight to netstat2.
+#[cfg(target_os = "linux")]
+fn with_sock_diag<T>(protocol: Protocol, ask: impl FnOnce() -> std::io::Result<T>) -> Option<T> {
+    let error = sock_diag_errors(protocol);
+    if error.get().is_some() {
+        return None;
+    }
+    ask().map_err(|e| error.set(e.to_string())).ok()
+}
+
+/// Reads the sockets of `protocols`. On Linux the kernel is asked over
+/// sock_diag for just the TCP states needed; elsewhere, or for a protocol it
+/// has failed for, netstat2 returns every socket and callers filter with
+/// `bound_port`.
+fn read_sockets(protocols: &[Protocol], states: SocketStates) -> Result<Vec<Socket>, String> {
+    let mut sockets = Vec::new();
+    let mut fallback = Vec::new();
+    #[cfg(target_os = "linux")]
+    {
+        let mask = match states {
+            SocketStates::Listening => netlink::LISTEN,
+            SocketStates::WithConnections => netlink::LISTEN | netlink::CONNECTIONS,
+        };
+        for &protocol in protocols {
+            match with_sock_diag(protocol, || netlink::sockets(protocol, mask)) {
+                Some(found) => sockets.extend(found),
+                None => fallback.push(protocol),
+            }
         }
+        netlink::resolve_owners(&mut sockets);
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = states;
+        fallback.extend_from_slice(protocols);
+    }
+    if fallback.is_empty() {
+        return Ok(sockets);
+    }
 
-        for pid in socket.associated_pids {
-            let pid_u32 = pid as u32;
-            let pid_sys = Pid::from_u32(pid_u32);
-            let proc = system.process(pid_sys);
-            let process_name = proc.map(|p| p.name().to_string_lossy().to_string());
-            let started_seconds_ago = proc.map(|p| p.run_time());
-            let container = docker_ports.get(&port);
-            out.push(ListenerInfo {
-                port,
-                pid: pid_u32,
-                process_name,
-                started_seconds_ago,
-                container_id: container.map(|c| c.id.clone()),
-                container_name: container.map(|c| c.name.clone()),
-            });
+    let flags = fallback
+        .iter()
+        .fold(ProtocolFlags::empty(), |flags, protocol| {
+            flags | protocol.flags()
+        });
+    let found = get_sockets_info(AddressFamilyFlags::IPV4 | AddressFamilyFlags::IPV6, flags)
+        .map_err(|e| e.to_string())?;
+    sockets.extend(found.into_iter().map(|info| Socket { info, queue: None }));
+    Ok(sockets)
+}
+
+/// Returns the local address of a socket that accepts traffic: TCP sockets in
+/// LISTEN state and every bound UDP socket (UDP has no listen state). On
+/// Linux, sock_diag has already dropped UDP sockets connected to a peer;
+/// netstat2 doesn't report the peer, so elsewhere they are kept.
+fn bound_port(info: &ProtocolSocketInfo) -> Option<(Protocol, IpAddr, u16)> {
+    match info {
+        ProtocolSocketInfo::Tcp(tcp) if tcp.state == TcpState::Listen => {
+            Some((Protocol::Tcp, tcp.local_addr, tcp.local_port))
         }
+        ProtocolSocketInfo::Udp(udp) => Some((Protocol::Udp, udp.local_addr, udp.local_port)),
+        _ => None,
     }
+}
 
-    Ok(out)
+/// The managed state a disconnect reads and records into, taken as one
+/// command argument.
+struct DisconnectState<'r> {
+    recent: State<'r, RecentKills>,
+    stopped: State<'r, RecentStops>,
+    runtime_timeout: State<'r, RuntimeTimeout>,
+    protected: State<'r, ProtectedList>,
 }
 
-fn docker_published_containers_by_port() -> HashMap<u16, DockerPublishedContainer> {
-    let output = match docker_output(&["ps", "--format", "{{json .}}"]) {
-        Ok(output) => output,
-        Err(_) => return HashMap::new(),
+impl<'de, R: tauri::Runtime> CommandArg<'de, R> for DisconnectState<'de> {
+    fn from_command(command: CommandItem<'de, R>) -> Result<Self, InvokeError> {
+        let state = command.message.state_ref();
+        Ok(Self {
+            recent: state.get(),
+            stopped: state.get(),
+            runtime_timeout: state.get(),
+            protected: state.get(),
+        })
+    }
+}
+
+#[tauri::command(async)]
+fn disconnect_listener(
+    port: u16,
+    protocol: Protocol,
+    targets: Vec<ListenerIdentity>,
+    containers: Vec<String>,
+    options: Option<KillOptions>,
+    state: DisconnectState<'_>,
+) -> Result<String, KillError> {
+    let options = options.unwrap_or_default();
+    let protected = state.protected.entries();
+    let pids: Vec<u32> = targets.iter().map(|target| target.pid).collect();
+    let policy = options.policy_for(&pids)?;
+
+    let timeout = state.runtime_timeout.get();
+    let (running, unavailable) = running_containers(timeout);
+    let proxies = proxies_for(&pids);
+    let owners = port_containers(port, protocol, &pids, &proxies, &running);
+    if owners.is_empty() && !proxies.is_empty() {
+        // Killing the proxy would only cut the container off the port.
+        let reason = match unavailable.first() {
+            Some(runtime) => runtime.message.clone(),
+            None => "no running container runtime lists it".to_string(),
+        };
+        return Err(format!("port {port} is forwarded to a container, but {reason}").into());
+    }
+
+    verify_identities(port, protocol, &targets)?;
+    let what = if owners.is_empty() {
+        if pids.is_empty() {
+            return Err(format!("no processes own port {port}").into());
+        }
+        check_protected(&pids, &protected, protection_reason)?;
+        terminate(
+            &pids,
+            &options,
+            &policy,
+            &protected,
+            Some(state.recent.inner()),
+        )?;
+        format!("killed {}", describe_pids(&pids))
+    } else {
+        verify_containers(port, &owners, &containers)?;
+        let held = ListeningPort { port, protocol };
+        let stopped =
+            stop_containers(&owners, held, &options, timeout, &protected, &state.stopped)?;
+        format!("stopped {stopped}")
     };
+    if !wait_until_port_closes(port, protocol, policy.port_close_timeout_ms) {
+        return Err(format!("{what}, but port {port} is still listening").into());
+    }
+    Ok(what)
+}
 
-    if !output.status.success() {
-        return HashMap::new();
+/// The running containers holding a port: every one publishing it, or, when
+/// none does, the host-network container its processes run in or the one
+/// their proxy forwards to. Those are stopped rather than killed inside so a
+/// restart policy doesn't bring them straight back. One no runtime lists
+/// (found through its cgroup alone) can't be stopped, so its processes are
+/// killed instead.
+fn port_containers(
+    port: u16,
+    protocol: Protocol,
+    pids: &[u32],
+    proxies: &[Proxy],
+    running: &[(RuntimeKind, Container)],
+) -> Vec<PublishedContainer> {
+    let candidates: Vec<PublishedContainer> =
+        match group_by_host_port(running.to_vec()).remove(&(port, protocol)) {
+            Some(published) => published
+                .into_iter()
+                .map(|(container, _)| container)
+                .collect(),
+            None => {
+                let mut owners: Vec<PublishedContainer> = pids
+                    .iter()
+                    .filter_map(|&pid| process_container(pid, running))
+                    .collect();
+                owners.extend(
+                    proxies
+                        .iter()
+                        .filter_map(|proxy| proxied_container(proxy, running)),
+                );
+                owners.retain(|owner| running.iter().any(|(_, c)| c.id == owner.id));
+                owners
+            }
+        };
+    let mut owners: Vec<PublishedContainer> = Vec::new();
+    for container in candidates {
+        if !owners.iter().any(|owner| owner.id == container.id) {
+            owners.push(container);
+        }
     }
+    owners
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut containers = HashMap::new();
-    for line in stdout.lines().filter(|line| !line.trim().is_empty()) {
-        let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+/// Stops every container in `owners` (or its compose service or project), as
+/// every one bound to the port has to go for it to close, and remembers them
+/// for restart. Returns what was stopped.
+fn stop_containers(
+    owners: &[PublishedContainer],
+    held: ListeningPort,
+    options: &KillOptions,
+    timeout: Duration,
+    protected: &[ProtectedMatcher],
+    stopped: &RecentStops,
+) -> Result<String, KillError> {
+    let mut plans = Vec::new();
+    for container in owners {
+        let runtime = runtime::runtime(container.runtime, timeout).map_err(|e| e.to_string())?;
+        let running = runtime.containers().map_err(|e| e.to_string())?;
+        let targets = containers_to_stop(running, container, options.container_scope)?;
+        plans.push((container, runtime, targets));
+    }
+    // Stopping a container signals everything in it.
+    let inside: Vec<u32> = plans
+        .iter()
+        .flat_map(|(_, _, targets)| targets)
+        .flat_map(|target| container_processes(&target.id))
+        .collect();
+    check_protected(&inside, protected, user_protection_reason)?;
+
+    let mut done: HashSet<String> = HashSet::new();
+    let mut stopped_what = Vec::new();
+    for (container, runtime, targets) in &plans {
+        // Replicas of one service share a plan; it is stopped and named once.
+        if targets.iter().all(|target| done.contains(&target.id)) {
             continue;
+        }
+        for target in targets {
+            if !done.insert(target.id.clone()) {
+                continue;
+            }
+            stop_container(runtime.as_ref(), target)?;
+            let mut listening = published_ports(target);
+            if target.id == container.id && listening.is_empty() {
+                // Host networking: the port it held is the one to wait for.
+                listening.push(held);
+            }
+            stopped.record(StoppedContainer {
+                id: target.id.clone(),
+                name: target.name.clone(),
+                runtime: container.runtime,
+                listening,
+                stopped_at: unix_now(),
+            });
+        }
+        stopped_what.push(match (options.container_scope, &container.compose) {
+            (ContainerScope::Service, Some(compose)) => format!(
+                "{} compose service {} ({} containers)",
+                container.runtime.label(),
+                compose.service,
+                targets.len()
+            ),
+            (ContainerScope::Project, Some(compose)) => format!(
+                "{} compose project {} ({} containers)",
+                container.runtime.label(),
+                compose.project,
+                targets.len()
+            ),
+            _ => format!("{} container {}", container.runtime.label(), container.name),
+        });
+    }
+    Ok(stopped_what.join(", "))
+}
+
+/// The pids of a worker pool that are remembered for undo. Workers a parent
+/// in the pool forked come back when it is relaunched, so only pids whose
+/// parent is outside the pool count: the parent of a pre-fork pool, or every
+/// one of a pool of siblings.
+fn pool_roots(pids: &[u32], parents: &HashMap<u32, u32>) -> Vec<u32> {
+    pids.iter()
+        .copied()
+        .filter(|pid| !parents.get(pid).is_some_and(|parent| pids.contains(parent)))
+        .collect()
+}
+
+/// Refuses the disconnect unless every target is still the same process (same
+/// start time) holding the same socket as when the UI scanned it.
+fn verify_identities(
+    port: u16,
+    protocol: Protocol,
+    targets: &[ListenerIdentity],
+) -> Result<(), KillError> {
+    let sockets = read_sockets(&[protocol], SocketStates::Listening)?;
+    let bound: Vec<(u32, Option<u64>)> = sockets
+        .iter()
+        .map(|socket| &socket.info)
+        .filter(|socket| {
+            matches!(
+                bound_port(&socket.protocol_socket_info),
+                Some((p, _, local_port)) if p == protocol && local_port == port
+            )
+        })
+        .flat_map(|socket| {
+            let inode = socket_inode(socket);
+            socket.associated_pids.iter().map(move |&pid| (pid, inode))
+        })
+        .collect();
+
+    let sys_pids: Vec<Pid> = targets.iter().map(|t| Pid::from_u32(t.pid)).collect();
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&sys_pids), true);
+
+    for target in targets {
+        let Some(proc) = system.process(Pid::from_u32(target.pid)) else {
+            return Err(KillError::no_such_process(target.pid));
         };
-        let id = value
-            .get("ID")
-            .and_then(|v| v.as_str())
-            .unwrap_or_default()
-            .to_string();
-        let name = value
-            .get("Names")
-            .and_then(|v| v.as_str())
-            .unwrap_or(&id)
-            .to_string();
-        let ports = value
-            .get("Ports")
-            .and_then(|v| v.as_str())
-            .unwrap_or_default();
-
-        if id.is_empty() {
-            continue;
+        if !target.matches(Some(proc.start_time()), &bound) {
+            return Err(KillError::identity_mismatch(target.pid, port));
         }
+    }
+    Ok(())
+}
 
-        for port in host_ports_from_docker_ports(ports) {
-            containers
-                .entry(port)
-                .or_insert_with(|| DockerPublishedContainer {
-                    id: id.clone(),
-                    name: name.clone(),
-                });
+/// Refuses the disconnect if a container the scan didn't show on the port,
+/// such as a replacement started since, now holds it.
+fn verify_containers(
+    port: u16,
+    owners: &[PublishedContainer],
+    scanned: &[String],
+) -> Result<(), KillError> {
+    match owners.iter().find(|owner| !scanned.contains(&owner.id)) {
+        Some(owner) => Err(KillError::ContainerMismatch {
+            message: format!(
+                "container {} has taken port {port} since the scan",
+                owner.name
+            ),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Refuses the kill if `reason` finds any of `pids` protected: by the built-in
+/// or user list (`protection_reason`), or, for processes inside a container,
+/// the user list alone (`user_protection_reason`).
+fn check_protected(
+    pids: &[u32],
+    protected: &[ProtectedMatcher],
+    reason: fn(&ProcessFacts, &[ProtectedMatcher]) -> Option<String>,
+) -> Result<(), KillError> {
+    let sys_pids: Vec<Pid> = pids.iter().map(|&pid| Pid::from_u32(pid)).collect();
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&sys_pids),
+        true,
+        ProcessRefreshKind::nothing()
+            .with_exe(UpdateKind::OnlyIfNotSet)
+            .with_user(UpdateKind::OnlyIfNotSet),
+    );
+    let users = Users::new_with_refreshed_list();
+
+    for &pid in pids {
+        let proc = system.process(Pid::from_u32(pid));
+        let facts = ProcessFacts {
+            pid,
+            name: proc.map(|proc| proc.name().to_string_lossy().to_string()),
+            exe_path: proc
+                .and_then(|proc| proc.exe())
+                .map(|path| path.to_string_lossy().to_string()),
+            user: proc
+                .and_then(|proc| proc.user_id())
+                .and_then(|uid| users.get_user_by_id(uid))
+                .map(|user| user.name().to_string()),
+        };
+        if let Some(reason) = reason(&facts, protected) {
+            return Err(KillError::protected(pid, &reason));
         }
     }
+    Ok(())
+}
 
-    containers
+/// Names and command lines of the processes about to be disconnected, for
+/// matching escalation rules.
+fn describe_for_rules(pids: &[u32]) -> Vec<ProcessDescription> {
+    let sys_pids: Vec<Pid> = pids.iter().map(|&pid| Pid::from_u32(pid)).collect();
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&sys_pids),
+        true,
+        ProcessRefreshKind::nothing().with_cmd(UpdateKind::Always),
+    );
+    sys_pids
+        .iter()
+        .filter_map(|pid| system.process(*pid))
+        .map(|proc| ProcessDescription {
+            name: proc.name().to_string_lossy().to_string(),
+            program: proc
+                .cmd()
+                .first()
+                .and_then(|arg0| Path::new(arg0).file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            command_line: proc
+                .cmd()
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+        })
+        .collect()
 }
 
-fn host_ports_from_docker_ports(ports: &str) -> Vec<u16> {
-    ports
-        .split(',')
-        .filter_map(|segment| segment.trim().split("->").next())
-        .flat_map(parse_docker_host_ports)
+/// The userland proxies among the processes about to be disconnected.
+fn proxies_for(pids: &[u32]) -> Vec<Proxy> {
+    let sys_pids: Vec<Pid> = pids.iter().map(|&pid| Pid::from_u32(pid)).collect();
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&sys_pids),
+        true,
+        ProcessRefreshKind::nothing().with_cmd(UpdateKind::Always),
+    );
+    sys_pids
+        .iter()
+        .filter_map(|pid| system.process(*pid))
+        .filter_map(|proc| {
+            let command_line: Vec<String> = proc
+                .cmd()
+                .iter()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect();
+            proxy_of(&proc.name().to_string_lossy(), &command_line)
+        })
         .collect()
 }
 
-fn parse_docker_host_ports(host: &str) -> Vec<u16> {
-    let Some(port_part) = host.trim().rsplit(':').next() else {
-        return Vec::new();
-    };
-    let port_part = port_part.trim();
-    if let Some((start, end)) = port_part.split_once('-') {
-        let Ok(start) = start.parse::<u16>() else {
-            return Vec::new();
-        };
-        let Ok(end) = end.parse::<u16>() else {
-            return Vec::new();
-        };
-        let (start, end) = if start <= end {
-            (start, end)
-        } else {
-            (end, start)
-        };
-        return (start..=end).collect();
+fn describe_pids(pids: &[u32]) -> String {
+    let list = pids
+        .iter()
+        .map(|pid| pid.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if pids.len() == 1 {
+        format!("pid {list}")
+    } else {
+        format!("pids {list}")
+    }
+}
+
+/// A container a disconnect stopped, kept so `restart_container` can start it
+/// again.
+#[derive(Debug, Clone, Serialize)]
+struct StoppedContainer {
+    id: String,
+    name: String,
+    runtime: RuntimeKind,
+    /// Host ports it published, to wait for after starting it again.
+    listening: Vec<ListeningPort>,
+    /// Unix time in seconds.
+    stopped_at: u64,
+}
+
+/// Recently stopped containers, newest first, one entry per container.
+#[derive(Default)]
+struct RecentStops(Mutex<Vec<StoppedContainer>>);
+
+impl RecentStops {
+    fn record(&self, stopped: StoppedContainer) {
+        let mut entries = self.0.lock().unwrap();
+        entries.retain(|entry| entry.id != stopped.id);
+        entries.insert(0, stopped);
+        entries.truncate(MAX_UNDO_ENTRIES);
+    }
+
+    fn take(&self, id: &str) -> Option<StoppedContainer> {
+        let mut entries = self.0.lock().unwrap();
+        let index = entries.iter().position(|entry| entry.id == id)?;
+        Some(entries.remove(index))
+    }
+
+    fn list(&self) -> Vec<StoppedContainer> {
+        self.0.lock().unwrap().clone()
     }
-    port_part
-        .parse::<u16>()
-        .map(|port| vec![port])
-        .unwrap_or_default()
 }
 
 #[tauri::command]
-fn disconnect_listener(port: u16, pid: u32) -> Result<String, String> {
-    if let Some(container) = docker_published_containers_by_port().get(&port) {
-        stop_docker_container(container)?;
-        if !wait_until_port_closes(port, 1_500) {
+fn recently_stopped(stopped: State<'_, RecentStops>) -> Vec<StoppedContainer> {
+    stopped.list()
+}
+
+/// Starts a recently stopped container again and waits for its published
+/// ports to listen.
+#[tauri::command(async)]
+fn restart_container(
+    id: String,
+    stopped: State<'_, RecentStops>,
+    runtime_timeout: State<'_, RuntimeTimeout>,
+) -> Result<String, String> {
+    let container = stopped
+        .take(&id)
+        .ok_or_else(|| "that container is no longer in the recently stopped list".to_string())?;
+    if let Err(e) = runtime::runtime(container.runtime, runtime_timeout.get())
+        .and_then(|runtime| runtime.start(&container.id))
+    {
+        let message = format!("failed to start container {}: {e}", container.name);
+        stopped.record(container);
+        return Err(message);
+    }
+    let deadline = Instant::now() + Duration::from_millis(RESTART_TIMEOUT_MS);
+    for listening in &container.listening {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !wait_until_port_listens(
+            listening.port,
+            listening.protocol,
+            remaining.as_millis() as u64,
+        ) {
             return Err(format!(
-                "stopped container {}, but port {} is still listening",
-                container.name, port
+                "started {} container {}, but port {} is not listening yet",
+                container.runtime.label(),
+                container.name,
+                listening.port
             ));
         }
-        return Ok(format!("stopped container {}", container.name));
     }
+    Ok(format!(
+        "started {} container {}",
+        container.runtime.label(),
+        container.name
+    ))
+}
 
-    kill_pid(pid)?;
-    if !wait_until_port_closes(port, 1_500) {
-        return Err(format!(
-            "killed pid {}, but port {} is still listening",
-            pid, port
-        ));
+fn published_ports(container: &Container) -> Vec<ListeningPort> {
+    let mut ports: Vec<ListeningPort> = container
+        .ports
+        .iter()
+        .filter_map(|binding| {
+            Some(ListeningPort {
+                port: binding.host_port?,
+                protocol: binding.protocol,
+            })
+        })
+        .collect();
+    ports.sort();
+    ports.dedup();
+    ports
+}
+
+fn stop_container(runtime: &dyn ContainerRuntime, container: &Container) -> Result<(), String> {
+    let Err(stop_err) = runtime.stop(&container.id, 2) else {
+        return Ok(());
+    };
+    runtime.kill(&container.id).map_err(|kill_err| {
+        format!(
+            "failed to stop container {}: {stop_err}; killing it failed too: {kill_err}",
+            container.name
+        )
+    })
+}
+
+/// How a kill or disconnect is carried out. Every field may be left out.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct KillOptions {
+    scope: KillScope,
+    /// What to stop when the port belongs to a container.
+    container_scope: ContainerScope,
+    /// Overrides the escalation rules when set.
+    policy: Option<EscalationPolicy>,
+    rules: Vec<EscalationRule>,
+}
+
+impl KillOptions {
+    fn policy_for(&self, pids: &[u32]) -> Result<EscalationPolicy, String> {
+        let policy = resolve_policy(self.policy.clone(), &self.rules, &describe_for_rules(pids));
+        policy.validate()?;
+        Ok(policy)
     }
-    Ok(format!("killed pid {}", pid))
 }
 
-fn stop_docker_container(container: &DockerPublishedContainer) -> Result<(), String> {
-    let stop = docker_output(&["stop", "--timeout", "2", &container.id])
-        .map_err(|e| format!("docker stop failed to start: {e}"))?;
+/// How much of the process tree around a pid to take down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum KillScope {
+    /// Only the pid itself.
+    #[default]
+    Process,
+    /// The pid's job: climb to its process-group leader (the npm, nodemon or
+    /// turbo supervisor that would respawn it) and take every descendant.
+    Tree,
+    /// Every process in the pid's process group.
+    Group,
+}
+
+/// How much to stop when a disconnected port belongs to a container.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ContainerScope {
+    /// Only the container publishing the port.
+    #[default]
+    Container,
+    /// Every container of its compose service.
+    Service,
+    /// Every container of its compose project.
+    Project,
+}
 
-    if stop.status.success() {
-        return Ok(());
+/// Why a kill or disconnect failed. Serialized with a `kind` tag so the UI can
+/// explain the common cases instead of showing a raw errno.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum KillError {
+    NoSuchProcess {
+        pid: u32,
+        message: String,
+    },
+    PermissionDenied {
+        pid: u32,
+        message: String,
+    },
+    IdentityMismatch {
+        pid: u32,
+        message: String,
+    },
+    /// The pid, or a member of its tree or group, is on the protected list.
+    Protected {
+        pid: u32,
+        message: String,
+    },
+    /// The port is held by a different container than the scan showed.
+    ContainerMismatch {
+        message: String,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+impl KillError {
+    fn no_such_process(pid: u32) -> Self {
+        KillError::NoSuchProcess {
+            pid,
+            message: format!("PID {pid} is no longer running"),
+        }
     }
 
-    let kill = docker_output(&["kill", &container.id])
-        .map_err(|e| format!("docker kill failed to start: {e}"))?;
+    fn permission_denied(pid: u32) -> Self {
+        KillError::PermissionDenied {
+            pid,
+            message: format!("not permitted to signal PID {pid}"),
+        }
+    }
 
-    if kill.status.success() {
-        Ok(())
-    } else {
-        let stop_err = String::from_utf8_lossy(&stop.stderr);
-        let kill_err = String::from_utf8_lossy(&kill.stderr);
-        Err(format!(
-            "docker stop failed for {} (exit {:?}): {}; docker kill failed (exit {:?}): {}",
-            container.name,
-            stop.status.code(),
-            stop_err.trim(),
-            kill.status.code(),
-            kill_err.trim()
-        ))
-    }
-}
-
-fn docker_output(args: &[&str]) -> std::io::Result<Output> {
-    let candidates = [
-        "docker",
-        "/opt/homebrew/bin/docker",
-        "/usr/local/bin/docker",
-        "/Applications/Docker.app/Contents/Resources/bin/docker",
-    ];
-    let mut last_error = None;
-
-    for candidate in candidates {
-        match Command::new(candidate).args(args).output() {
-            Ok(output) => return Ok(output),
-            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
-                last_error = Some(error);
-            }
-            Err(error) => return Err(error),
+    fn identity_mismatch(pid: u32, port: u16) -> Self {
+        KillError::IdentityMismatch {
+            pid,
+            message: format!("PID {pid} is no longer the process listening on port {port}"),
+        }
+    }
+
+    fn protected(pid: u32, reason: &str) -> Self {
+        KillError::Protected {
+            pid,
+            message: format!("refusing to kill PID {pid}: {reason}"),
         }
     }
+}
+
+impl From<String> for KillError {
+    fn from(message: String) -> Self {
+        KillError::Failed { message }
+    }
+}
+
+/// How many killed processes, and separately stopped containers, are kept for
+/// undo.
+const MAX_UNDO_ENTRIES: usize = 20;
+/// How long `restart_killed` waits for a relaunched process to listen again.
+const RESTART_TIMEOUT_MS: u64 = 15_000;
+
+/// A process Port-o-Potty killed, with what it takes to start it again.
+#[derive(Debug, Clone, Serialize)]
+struct KilledProcess {
+    id: u64,
+    pid: u32,
+    process_name: String,
+    command_line: Vec<String>,
+    cwd: String,
+    /// The ports it held, which a restart waits to see listening again.
+    listening: Vec<ListeningPort>,
+    /// Unix time in seconds.
+    killed_at: u64,
+    #[serde(skip)]
+    snapshot: ProcessSnapshot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct ListeningPort {
+    port: u16,
+    protocol: Protocol,
+}
 
-    Err(last_error.unwrap_or_else(|| {
-        std::io::Error::new(std::io::ErrorKind::NotFound, "docker command not found")
-    }))
+/// Recently killed processes, newest first.
+#[derive(Default)]
+struct RecentKills {
+    next_id: AtomicU64,
+    entries: Mutex<Vec<KilledProcess>>,
+}
+
+impl RecentKills {
+    fn record(&self, mut killed: KilledProcess) {
+        killed.id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.restore(killed);
+    }
+
+    /// Puts an entry back, e.g. after a failed restart, keeping its id.
+    fn restore(&self, killed: KilledProcess) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(0, killed);
+        entries.truncate(MAX_UNDO_ENTRIES);
+    }
+
+    fn take(&self, id: u64) -> Option<KilledProcess> {
+        let mut entries = self.entries.lock().unwrap();
+        let index = entries.iter().position(|killed| killed.id == id)?;
+        Some(entries.remove(index))
+    }
+
+    fn list(&self) -> Vec<KilledProcess> {
+        self.entries.lock().unwrap().clone()
+    }
+}
+
+/// Snapshots `root` before it is killed so it can be relaunched, along with
+/// the ports any of `targets` is listening on. `None` when the process can't
+/// be read (usually another user's), in which case there's no undo.
+fn capture_for_undo(root: u32, targets: &[u32]) -> Option<KilledProcess> {
+    let snapshot = relaunch::snapshot(root).ok()?;
+    Some(KilledProcess {
+        id: 0,
+        pid: root,
+        process_name: snapshot.program_name(),
+        command_line: snapshot
+            .command_line
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect(),
+        cwd: snapshot.cwd.to_string_lossy().to_string(),
+        listening: listening_ports(targets),
+        killed_at: unix_now(),
+        snapshot,
+    })
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
 }
 
+fn listening_ports(pids: &[u32]) -> Vec<ListeningPort> {
+    let Ok(sockets) = read_sockets(&[Protocol::Tcp, Protocol::Udp], SocketStates::Listening) else {
+        return Vec::new();
+    };
+    let mut ports: Vec<ListeningPort> = sockets
+        .iter()
+        .map(|socket| &socket.info)
+        .filter(|socket| socket.associated_pids.iter().any(|pid| pids.contains(pid)))
+        .filter_map(|socket| bound_port(&socket.protocol_socket_info))
+        .map(|(protocol, _, port)| ListeningPort { port, protocol })
+        .collect();
+    ports.sort();
+    ports.dedup();
+    ports
+}
+
+#[tauri::command]
+fn recently_killed(recent: State<'_, RecentKills>) -> Vec<KilledProcess> {
+    recent.list()
+}
+
+/// Relaunches a recently killed process detached, with its original argv, cwd
+/// and environment, and waits for its ports to listen again.
+#[tauri::command(async)]
+fn restart_killed(id: u64, recent: State<'_, RecentKills>) -> Result<String, String> {
+    let killed = recent
+        .take(id)
+        .ok_or_else(|| "that process is no longer in the recently killed list".to_string())?;
+    let mut child = match killed.snapshot.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let message = format!("failed to relaunch {}: {e}", killed.process_name);
+            recent.restore(killed);
+            return Err(message);
+        }
+    };
+    let pid = child.id();
+
+    let deadline = Instant::now() + Duration::from_millis(RESTART_TIMEOUT_MS);
+    let result = loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            let message = format!(
+                "relaunched {} but it exited ({status})",
+                killed.process_name
+            );
+            recent.restore(killed);
+            break Err(message);
+        }
+        let pending = killed
+            .listening
+            .iter()
+            .find(|listening| !port_has_listener(listening.port, listening.protocol));
+        match pending {
+            None => break Ok(format!("relaunched {} as pid {pid}", killed.process_name)),
+            Some(listening) if Instant::now() >= deadline => {
+                break Err(format!(
+                    "relaunched {} as pid {pid}, but port {} is not listening yet",
+                    killed.process_name, listening.port
+                ));
+            }
+            Some(_) => thread::sleep(Duration::from_millis(100)),
+        }
+    };
+    // Reap it whenever it exits so it never lingers as a zombie.
+    thread::spawn(move || child.wait());
+    result
+}
+
+#[tauri::command(async)]
+fn kill_pid(
+    pid: u32,
+    options: Option<KillOptions>,
+    recent: State<'_, RecentKills>,
+    protected: State<'_, ProtectedList>,
+) -> Result<(), KillError> {
+    let options = options.unwrap_or_default();
+    let policy = options.policy_for(&[pid])?;
+    terminate(
+        &[pid],
+        &options,
+        &policy,
+        &protected.entries(),
+        Some(recent.inner()),
+    )
+}
+
+/// Replaces the user's protected list, which every kill and disconnect checks.
 #[tauri::command]
-fn kill_pid(pid: u32) -> Result<(), String> {
-    if pid == 0 {
-        return Err("invalid pid".to_string());
+fn set_protected_processes(entries: Vec<ProtectedMatcher>, protected: State<'_, ProtectedList>) {
+    protected.set(entries);
+}
+
+/// Kills `pids` (the processes holding one port) and each one's tree or group
+/// in a single pass, after checking every target against the protected list.
+/// A master takes its workers down with it, so they are only required to be
+/// running before anything is signalled. With `recent`, the pool's roots are
+/// remembered so they can be restarted.
+fn terminate(
+    pids: &[u32],
+    options: &KillOptions,
+    policy: &EscalationPolicy,
+    protected: &[ProtectedMatcher],
+    recent: Option<&RecentKills>,
+) -> Result<(), KillError> {
+    if pids.is_empty() || pids.contains(&0) {
+        return Err("invalid pid".to_string().into());
     }
 
     #[cfg(windows)]
     {
-        let status = std::process::Command::new("taskkill")
-            .args(["/PID", &pid.to_string(), "/T", "/F"])
-            .status()
-            .map_err(|e| e.to_string())?;
-        if status.success() {
-            Ok(())
-        } else {
-            Err(format!("taskkill failed (exit {:?})", status.code()))
+        // taskkill /T /F always force-kills the whole tree, so neither the
+        // scope nor the signal steps apply.
+        let _ = policy;
+        let KillPlan { trees, parents } = kill_plan(pids, options.scope, protected)?;
+        check_protected(&merge_trees(&trees), protected, protection_reason)?;
+        let undo: Vec<KilledProcess> = match recent {
+            Some(_) => pool_roots(pids, &parents)
+                .into_iter()
+                .filter_map(|pid| capture_for_undo(pid, &[pid]))
+                .collect(),
+            None => Vec::new(),
+        };
+        // A pid already in an earlier tree goes down with it.
+        let roots = pids
+            .iter()
+            .enumerate()
+            .filter(|&(i, pid)| !trees[..i].iter().any(|tree| tree.contains(pid)));
+        for (n, (_, &root)) in roots.enumerate() {
+            match taskkill(root) {
+                // Siblings may exit along with the first tree.
+                Err(KillError::NoSuchProcess { .. }) if n > 0 => {}
+                result => result?,
+            }
+        }
+        if let Some(recent) = recent {
+            undo.into_iter().for_each(|killed| recent.record(killed));
         }
+        Ok(())
     }
 
     #[cfg(not(windows))]
     {
-        let term = Command::new("kill")
-            .args(["-TERM", &pid.to_string()])
-            .status()
-            .map_err(|e| e.to_string())?;
+        if let Some(&gone) = pids.iter().find(|&&pid| !signals::is_running(pid)) {
+            return Err(KillError::no_such_process(gone));
+        }
+        let KillPlan { trees, parents } = kill_plan(pids, options.scope, protected)?;
+        let targets = merge_trees(&trees);
+        check_protected(&targets, protected, protection_reason)?;
+
+        let remembered = pool_roots(pids, &parents);
+        let mut undo: Vec<KilledProcess> = Vec::new();
+        for (pid, tree) in pids.iter().zip(&trees) {
+            if recent.is_some()
+                && remembered.contains(pid)
+                && !undo.iter().any(|killed| killed.pid == tree[0])
+            {
+                undo.extend(capture_for_undo(tree[0], tree));
+            }
+        }
+        terminate_pids(&targets, policy)?;
+        if let Some(recent) = recent {
+            undo.into_iter().for_each(|killed| recent.record(killed));
+        }
+        Ok(())
+    }
+}
+
+/// What a kill of several pids signals.
+struct KillPlan {
+    /// For each pid, the pids its kill signals, root first.
+    trees: Vec<Vec<u32>>,
+    /// Each process's parent pid.
+    parents: HashMap<u32, u32>,
+}
 
-        if !term.success() {
-            return Err(format!("kill -TERM failed (exit {:?})", term.code()));
+fn kill_plan(
+    pids: &[u32],
+    scope: KillScope,
+    protected: &[ProtectedMatcher],
+) -> Result<KillPlan, KillError> {
+    let mut system = System::new();
+    if cfg!(windows) || scope != KillScope::Process {
+        system.refresh_processes(ProcessesToUpdate::All, true);
+    } else {
+        let sys_pids: Vec<Pid> = pids.iter().map(|&pid| Pid::from_u32(pid)).collect();
+        system.refresh_processes(ProcessesToUpdate::Some(&sys_pids), true);
+    }
+    let parents = parent_pids(&system);
+    let trees = pids
+        .iter()
+        .map(|&pid| {
+            #[cfg(windows)]
+            {
+                let _ = (scope, protected);
+                Ok(descendants(pid, &parents))
+            }
+            #[cfg(not(windows))]
+            kill_targets(pid, scope, &system, &parents, protected)
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(KillPlan { trees, parents })
+}
+
+/// Every pid of `trees` once, in order.
+fn merge_trees(trees: &[Vec<u32>]) -> Vec<u32> {
+    let mut merged: Vec<u32> = Vec::new();
+    for &pid in trees.iter().flatten() {
+        if !merged.contains(&pid) {
+            merged.push(pid);
         }
+    }
+    merged
+}
 
-        if wait_until_pid_exits(pid, 800) {
+/// What a kill of `pids` with `scope` would take down.
+#[derive(Debug, Clone, Serialize)]
+struct KillPreview {
+    root_pid: u32,
+    root_name: Option<String>,
+    count: usize,
+}
+
+/// Names the top of the tree or group a kill would take down, so the UI can
+/// show it before the user confirms.
+#[tauri::command(async)]
+fn preview_kill(
+    pids: Vec<u32>,
+    scope: KillScope,
+    protected: State<'_, ProtectedList>,
+) -> Result<KillPreview, KillError> {
+    let targets = merge_trees(&kill_plan(&pids, scope, &protected.entries())?.trees);
+    let root_pid = *targets
+        .first()
+        .ok_or_else(|| "no processes to kill".to_string())?;
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[Pid::from_u32(root_pid)]), true);
+    Ok(KillPreview {
+        root_pid,
+        root_name: system
+            .process(Pid::from_u32(root_pid))
+            .map(|proc| proc.name().to_string_lossy().to_string()),
+        count: targets.len(),
+    })
+}
+
+/// Force-kills `pid` and its tree.
+#[cfg(windows)]
+fn taskkill(pid: u32) -> Result<(), KillError> {
+    let output = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("not found") {
+        Err(KillError::no_such_process(pid))
+    } else if stderr.contains("Access is denied") {
+        Err(KillError::permission_denied(pid))
+    } else {
+        Err(format!(
+            "taskkill failed (exit {:?}): {}",
+            output.status.code(),
+            stderr.trim()
+        )
+        .into())
+    }
+}
+
+/// Walks the policy's signal steps, signalling whichever pids are still alive
+/// (supervisors first, so they don't respawn their children) and waiting up
+/// to each step's timeout for all of them to exit.
+#[cfg(not(windows))]
+fn terminate_pids(pids: &[u32], policy: &EscalationPolicy) -> Result<(), KillError> {
+    let mut remaining = pids.to_vec();
+    let mut last_signal = None;
+    for step in &policy.steps {
+        remaining.retain(|&pid| signals::is_running(pid));
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        for &pid in &remaining {
+            match signals::send(pid, step.signal) {
+                // A tree member may exit on its own before we get to it.
+                Ok(()) | Err(SignalError::NoSuchProcess) => {}
+                Err(SignalError::PermissionDenied) => {
+                    return Err(KillError::permission_denied(pid));
+                }
+                Err(SignalError::Other(error)) => {
+                    return Err(
+                        format!("SIG{} to PID {pid} failed: {error}", step.signal.name()).into(),
+                    );
+                }
+            }
+        }
+        last_signal = Some(step.signal);
+        if signals::wait_for_exit(&remaining, Duration::from_millis(step.timeout_ms)) {
             return Ok(());
         }
+    }
+
+    remaining.retain(|&pid| signals::is_running(pid));
+    match last_signal {
+        Some(signal) if !remaining.is_empty() => Err(format!(
+            "{} accepted SIG{} but still running",
+            describe_pids(&remaining),
+            signal.name()
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
 
-        let kill = Command::new("kill")
-            .args(["-KILL", &pid.to_string()])
-            .status()
-            .map_err(|e| e.to_string())?;
+/// Resolves the pids a kill of `pid` should signal, ordered parents first.
+/// Port-o-Potty and its own ancestors are never included. `system` must hold
+/// every process unless `scope` is `Process`.
+#[cfg(not(windows))]
+fn kill_targets(
+    pid: u32,
+    scope: KillScope,
+    system: &System,
+    parents: &HashMap<u32, u32>,
+    protected: &[ProtectedMatcher],
+) -> Result<Vec<u32>, KillError> {
+    if scope == KillScope::Process {
+        return Ok(vec![pid]);
+    }
 
-        if kill.success() && wait_until_pid_exits(pid, 800) {
-            Ok(())
-        } else if kill.success() {
-            Err(format!("PID {pid} accepted SIGKILL but is still running"))
-        } else {
-            Err(format!("kill failed (exit {:?})", kill.code()))
+    let targets = match scope {
+        KillScope::Process => vec![pid],
+        KillScope::Tree => {
+            let leave = |parent: u32| {
+                signals::session_of(parent) == Some(parent)
+                    || check_protected(&[parent], protected, protection_reason).is_err()
+            };
+            descendants(
+                group_root(pid, parents, signals::process_group, leave),
+                parents,
+            )
         }
+        KillScope::Group => {
+            let pgid = signals::process_group(pid).ok_or(KillError::no_such_process(pid))?;
+            let mut members: Vec<u32> = system
+                .processes()
+                .keys()
+                .map(|pid| pid.as_u32())
+                .filter(|&member| signals::process_group(member) == Some(pgid))
+                .collect();
+            members.sort_by_key(|&member| (member != pgid, member));
+            members
+        }
+    };
+
+    let own = ancestors_and_self(std::process::id(), parents);
+    let targets: Vec<u32> = targets
+        .into_iter()
+        .filter(|pid| *pid > 1 && !own.contains(pid))
+        .collect();
+    if targets.is_empty() {
+        return Err(KillError::protected(
+            pid,
+            "its tree contains Port-o-Potty itself",
+        ));
     }
+    Ok(targets)
 }
 
+/// Walks up from `pid` through parents in the same process group, stopping at
+/// the group leader or below a parent to `leave` alone. A task runner or IDE
+/// that launched the job without job control can lead its group; it is left
+/// out when it leads its session or is protected.
 #[cfg(not(windows))]
-fn wait_until_pid_exits(pid: u32, timeout_ms: u64) -> bool {
+fn group_root(
+    pid: u32,
+    parents: &HashMap<u32, u32>,
+    pgid_of: impl Fn(u32) -> Option<u32>,
+    leave: impl Fn(u32) -> bool,
+) -> u32 {
+    let Some(pgid) = pgid_of(pid) else {
+        return pid;
+    };
+    let mut root = pid;
+    for _ in 0..parents.len() {
+        if root == pgid {
+            break;
+        }
+        match parents.get(&root) {
+            Some(&parent) if parent > 1 && pgid_of(parent) == Some(pgid) && !leave(parent) => {
+                root = parent
+            }
+            _ => break,
+        }
+    }
+    root
+}
+
+/// Each process's parent pid.
+fn parent_pids(system: &System) -> HashMap<u32, u32> {
+    system
+        .processes()
+        .iter()
+        .filter_map(|(pid, proc)| Some((pid.as_u32(), proc.parent()?.as_u32())))
+        .collect()
+}
+
+/// `root` followed by all of its descendants, breadth first.
+fn descendants(root: u32, parents: &HashMap<u32, u32>) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (&child, &parent) in parents {
+        children.entry(parent).or_default().push(child);
+    }
+
+    let mut out = vec![root];
+    let mut next = 0;
+    while next < out.len() {
+        if let Some(kids) = children.get_mut(&out[next]) {
+            kids.sort_unstable();
+            for &kid in kids.iter() {
+                if !out.contains(&kid) {
+                    out.push(kid);
+                }
+            }
+        }
+        next += 1;
+    }
+    out
+}
+
+#[cfg(not(windows))]
+fn ancestors_and_self(pid: u32, parents: &HashMap<u32, u32>) -> Vec<u32> {
+    let mut out = vec![pid];
+    while let Some(&parent) = parents.get(out.last().unwrap()) {
+        if out.contains(&parent) {
+            break;
+        }
+        out.push(parent);
+    }
+    out
+}
+
+fn wait_until_port_closes(port: u16, protocol: Protocol, timeout_ms: u64) -> bool {
     let attempts = (timeout_ms / 100).max(1);
     for _ in 0..attempts {
-        if !pid_is_running(pid) {
+        if !port_has_listener(port, protocol) {
             return true;
         }
         thread::sleep(Duration::from_millis(100));
     }
-    !pid_is_running(pid)
-}
-
-#[cfg(not(windows))]
-fn pid_is_running(pid: u32) -> bool {
-    Command::new("kill")
-        .args(["-0", &pid.to_string()])
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
+    !port_has_listener(port, protocol)
 }
 
-fn wait_until_port_closes(port: u16, timeout_ms: u64) -> bool {
+fn wait_until_port_listens(port: u16, protocol: Protocol, timeout_ms: u64) -> bool {
     let attempts = (timeout_ms / 100).max(1);
     for _ in 0..attempts {
-        if !port_has_listener(port) {
+        if port_has_listener(port, protocol) {
             return true;
         }
         thread::sleep(Duration::from_millis(100));
     }
-    !port_has_listener(port)
+    port_has_listener(port, protocol)
 }
 
-fn port_has_listener(port: u16) -> bool {
-    let Ok(sockets) = get_sockets_info(AddressFamilyFlags::IPV4 | AddressFamilyFlags::IPV6, ProtocolFlags::TCP) else {
+fn port_has_listener(port: u16, protocol: Protocol) -> bool {
+    #[cfg(target_os = "linux")]
+    if let Some(found) = with_sock_diag(protocol, || netlink::has_listener(port, protocol)) {
+        return found;
+    }
+
+    let Ok(sockets) = read_sockets(&[protocol], SocketStates::Listening) else {
         return false;
     };
 
-    sockets.into_iter().any(|socket| match socket.protocol_socket_info {
-        ProtocolSocketInfo::Tcp(tcp) => tcp.local_port == port && tcp.state == TcpState::Listen,
-        _ => false,
+    sockets.into_iter().any(|socket| {
+        matches!(
+            bound_port(&socket.info.protocol_socket_info),
+            Some((p, _, local_port)) if p == protocol && local_port == port
+        )
     })
 }
 
+const TRAY_ID: &str = "main";
+
 fn main() {
     tauri::Builder::default()
+        .manage(RecentKills::default())
+        .manage(RecentStops::default())
+        .manage(Monitor::default())
+        .manage(ListenTimes::default())
+        .manage(ProcessCache::default())
+        .manage(RuntimeTimeout::default())
+        .manage(ProtectedList::default())
         .setup(|app| {
             let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
             let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
             let menu = Menu::with_items(app, &[&show, &quit])?;
 
-            TrayIconBuilder::new()
+            TrayIconBuilder::with_id(TRAY_ID)
                 .icon(app.default_window_icon().unwrap().clone())
                 .icon_as_template(true)
                 .menu(&menu)
@@ -386,6 +2063,18 @@ fn main() {
                 })
                 .build(app)?;
 
+            let history = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| e.to_string())
+                .and_then(|dir| {
+                    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+                    History::open(&dir.join("history.sqlite3"), unix_now())
+                        .map_err(|e| e.to_string())
+                })
+                .unwrap_or_else(|e| History::unpersisted(e, unix_now()));
+            app.manage(history);
+            monitor::spawn(app.handle().clone());
             Ok(())
         })
         .on_window_event(|window, event| match event {
@@ -413,7 +2102,20 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             scan_ports,
             disconnect_listener,
-            kill_pid
+            kill_pid,
+            preview_kill,
+            configure_monitor,
+            listener_history,
+            listener_lifetimes,
+            history_retention,
+            history_unpersisted_reason,
+            set_history_retention,
+            recently_killed,
+            restart_killed,
+            recently_stopped,
+            restart_container,
+            set_runtime_timeout,
+            set_protected_processes
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -422,32 +2124,243 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use netstat2::{TcpSocketInfo, UdpSocketInfo};
+    use std::net::IpAddr;
 
     #[test]
-    fn extracts_ipv4_and_ipv6_published_docker_ports() {
-        let ports = "0.0.0.0:7777->8080/tcp, [::]:27017->27017/tcp";
+    fn reports_bound_udp_sockets_and_only_listening_tcp_sockets() {
+        let any = IpAddr::from([0, 0, 0, 0]);
+        let udp = ProtocolSocketInfo::Udp(UdpSocketInfo {
+            local_addr: any,
+            local_port: 5353,
+        });
+        let listening = ProtocolSocketInfo::Tcp(TcpSocketInfo {
+            local_addr: any,
+            local_port: 3000,
+            remote_addr: any,
+            remote_port: 0,
+            state: TcpState::Listen,
+        });
+        let established = ProtocolSocketInfo::Tcp(TcpSocketInfo {
+            local_addr: any,
+            local_port: 3000,
+            remote_addr: IpAddr::from([127, 0, 0, 1]),
+            remote_port: 51234,
+            state: TcpState::Established,
+        });
 
-        assert_eq!(host_ports_from_docker_ports(ports), vec![7777, 27017]);
+        assert_eq!(bound_port(&udp), Some((Protocol::Udp, any, 5353)));
+        assert_eq!(bound_port(&listening), Some((Protocol::Tcp, any, 3000)));
+        assert_eq!(bound_port(&established), None);
     }
 
     #[test]
-    fn ignores_unpublished_container_ports() {
-        assert!(host_ports_from_docker_ports("80/tcp, 443/tcp").is_empty());
+    fn classifies_bind_address_exposure() {
+        let exposure = |addr: &str| Exposure::of(addr.parse().unwrap());
+
+        assert_eq!(exposure("127.0.0.1"), Exposure::Loopback);
+        assert_eq!(exposure("::1"), Exposure::Loopback);
+        assert_eq!(exposure("::ffff:127.0.0.1"), Exposure::Loopback);
+        assert_eq!(exposure("0.0.0.0"), Exposure::AllInterfaces);
+        assert_eq!(exposure("::"), Exposure::AllInterfaces);
+        assert_eq!(exposure("192.168.1.20"), Exposure::Lan);
+        assert_eq!(exposure("fe80::1"), Exposure::Lan);
     }
 
     #[test]
-    fn expands_published_port_ranges() {
-        assert_eq!(
-            host_ports_from_docker_ports("0.0.0.0:8000-8003->80-83/tcp"),
-            vec![8000, 8001, 8002, 8003]
-        );
+    fn groups_dual_stack_sockets_and_worker_pools_into_one_listener() {
+        let process = |pid| ListenerProcess {
+            pid,
+            process_name: Some("nginx".to_string()),
+            ..ListenerProcess::default()
+        };
+        let loopback: IpAddr = "127.0.0.1".parse().unwrap();
+        let any_v6: IpAddr = "::".parse().unwrap();
+
+        let mut listener = ListenerInfo::new(8080, Protocol::Tcp, loopback);
+        listener.add_socket(any_v6);
+        listener.add_socket(loopback);
+        listener.add_socket(loopback);
+        listener.add_process(process(42));
+        listener.add_process(process(41));
+        listener.add_process(process(42));
+
+        let addrs: Vec<IpAddr> = listener.sockets.iter().map(|s| s.local_addr).collect();
+        let pids: Vec<u32> = listener.processes.iter().map(|p| p.pid).collect();
+        assert_eq!(addrs, vec![loopback, any_v6]);
+        assert_eq!(pids, vec![41, 42]);
+        assert_eq!(listener.exposure, Exposure::AllInterfaces);
     }
 
     #[test]
-    fn parses_localhost_published_ports() {
+    fn counts_connections_by_state_and_caps_peer_list() {
+        let peer = |state| ConnectionPeer {
+            remote_addr: IpAddr::from([127, 0, 0, 1]),
+            remote_port: 50000,
+            state,
+            pid: None,
+            process_name: None,
+        };
+        let mut summary = ConnectionSummary::default();
+        for _ in 0..MAX_PEERS {
+            summary.record(peer(ConnectionState::Established));
+        }
+        summary.record(peer(ConnectionState::TimeWait));
+        summary.record(peer(ConnectionState::CloseWait));
+
+        assert_eq!(summary.established, MAX_PEERS as u32);
+        assert_eq!(summary.time_wait, 1);
+        assert_eq!(summary.close_wait, 1);
+        assert_eq!(summary.peers.len(), MAX_PEERS);
+        assert_eq!(ConnectionState::from_tcp(TcpState::SynSent), None);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn tree_scope_climbs_to_the_group_leader_and_takes_all_descendants() {
+        // shell(100) -> npm(200, leader) -> sh(300) -> node(400) -> esbuild(500)
+        //                                \-> nodemon(310)
+        let parents: HashMap<u32, u32> =
+            [(200, 100), (300, 200), (310, 200), (400, 300), (500, 400)].into();
+        let pgid_of = |pid: u32| Some(if pid == 100 { 100 } else { 200 });
+
+        let root = group_root(400, &parents, pgid_of, |_| false);
+
+        assert_eq!(root, 200);
+        assert_eq!(descendants(root, &parents), vec![200, 300, 310, 400, 500]);
+        assert_eq!(ancestors_and_self(400, &parents), vec![400, 300, 200, 100]);
+
+        // The same job started by an IDE (200) that leads the group itself:
+        // the climb stops below it.
+        assert_eq!(group_root(400, &parents, pgid_of, |pid| pid == 200), 300);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kills_a_pool_whose_master_reaps_its_workers() {
+        // A master that takes its workers down with it on SIGTERM, as nginx
+        // and gunicorn do, so they are gone before they could be signalled
+        // one by one.
+        let mut master = std::process::Command::new("sh")
+            .args([
+                "-c",
+                "sleep 60 & sleep 60 & trap 'kill $(jobs -p); wait; exit 0' TERM; wait",
+            ])
+            .spawn()
+            .unwrap();
+        let master_pid = master.id();
+        let mut workers = Vec::new();
+        for _ in 0..100 {
+            let mut system = System::new();
+            system.refresh_processes(ProcessesToUpdate::All, true);
+            workers = parent_pids(&system)
+                .into_iter()
+                .filter(|&(_, parent)| parent == master_pid)
+                .map(|(pid, _)| pid)
+                .collect();
+            if workers.len() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(workers.len(), 2);
+        // Reap on another thread so the master doesn't linger as a zombie.
+        let reaper = thread::spawn(move || master.wait());
+
+        let mut pool = vec![master_pid];
+        pool.extend(&workers);
+        terminate(
+            &pool,
+            &KillOptions::default(),
+            &EscalationPolicy::default(),
+            &[],
+            None,
+        )
+        .unwrap();
+        reaper.join().unwrap().unwrap();
+        assert!(!pool.iter().any(|&pid| signals::is_running(pid)));
+    }
+
+    #[test]
+    fn refuses_containers_the_scan_did_not_show() {
+        let owner = |id: &str| PublishedContainer {
+            id: id.to_string(),
+            name: id.to_string(),
+            runtime: RuntimeKind::Docker,
+            compose: None,
+        };
+        let scanned = ["web-1".to_string(), "web-2".to_string()];
+
+        assert!(verify_containers(8080, &[owner("web-1"), owner("web-2")], &scanned).is_ok());
+        assert!(matches!(
+            verify_containers(8080, &[owner("web-1"), owner("web-3")], &scanned),
+            Err(KillError::ContainerMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn remembers_every_worker_not_forked_by_another_in_the_pool() {
+        // nginx: a master (100) and the workers it forked.
+        let prefork: HashMap<u32, u32> = [(100, 1), (101, 100), (102, 100)].into();
+        assert_eq!(pool_roots(&[100, 101, 102], &prefork), [100]);
+
+        // SO_REUSEPORT siblings started side by side by a supervisor (50).
+        let siblings: HashMap<u32, u32> = [(201, 50), (202, 50), (203, 50)].into();
+        assert_eq!(pool_roots(&[201, 202, 203], &siblings), [201, 202, 203]);
+    }
+
+    #[test]
+    fn listener_identity_round_trips_and_rejects_reused_pids() {
+        let identity = ListenerIdentity {
+            pid: 4242,
+            start_time: Some(1_718_000_000),
+            socket_inode: Some(91_822),
+        };
+        let token: String = identity.clone().into();
+        assert_eq!(token, "4242:1718000000:91822");
+        assert_eq!(ListenerIdentity::try_from(token), Ok(identity.clone()));
         assert_eq!(
-            host_ports_from_docker_ports("127.0.0.1:5432->5432/tcp"),
-            vec![5432]
+            ListenerIdentity::try_from("4242::".to_string()),
+            Ok(ListenerIdentity {
+                pid: 4242,
+                ..ListenerIdentity::default()
+            })
         );
+        assert!(ListenerIdentity::try_from("4242".to_string()).is_err());
+
+        let bound = [(4242, Some(91_822))];
+        assert!(identity.matches(Some(1_718_000_000), &bound));
+        // Same pid, different process.
+        assert!(!identity.matches(Some(1_718_000_900), &bound));
+        // Same process, but the port is now held by a different socket.
+        assert!(!identity.matches(Some(1_718_000_000), &[(4242, Some(99_000))]));
+        assert!(!identity.matches(Some(1_718_000_000), &[(5151, Some(91_822))]));
+    }
+
+    #[test]
+    fn remembers_each_stopped_container_once_newest_first() {
+        let stopped = |id: &str, port| StoppedContainer {
+            id: id.to_string(),
+            name: format!("{id}-name"),
+            runtime: RuntimeKind::Docker,
+            listening: vec![ListeningPort {
+                port,
+                protocol: Protocol::Tcp,
+            }],
+            stopped_at: 0,
+        };
+        let recent = RecentStops::default();
+        recent.record(stopped("api", 8080));
+        recent.record(stopped("db", 5432));
+        recent.record(stopped("api", 8081));
+
+        let ids: Vec<_> = recent
+            .list()
+            .into_iter()
+            .map(|c| (c.id, c.listening[0].port))
+            .collect();
+        assert_eq!(ids, [("api".to_string(), 8081), ("db".to_string(), 5432)]);
+        assert_eq!(recent.take("db").map(|c| c.listening[0].port), Some(5432));
+        assert!(recent.take("db").is_none());
     }
 }
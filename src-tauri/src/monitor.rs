@@ -0,0 +1,414 @@
+use crate::{
+    history::History, in_any_range, scan_ports, ConnectionSummary, ListenerInfo, PortRange,
+    Protocol, UnavailableRuntime,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Fastest rescan the UI may ask for; a scan walks every process.
+const MIN_INTERVAL_MS: u64 = 1_000;
+
+/// Fastest the UI's connection counts are refreshed. They move on nearly every
+/// scan of a busy port, so they are sent apart from the listener events.
+const CONNECTIONS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What the monitor scans and how often. Set by the UI through
+/// `configure_monitor`; nothing is scanned until it has been.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonitorConfig {
+    pub ranges: Vec<PortRange>,
+    pub interval_ms: u64,
+}
+
+/// What the monitor thread watches, as last set by `configure_monitor`.
+#[derive(Default)]
+pub struct Monitor {
+    config: Mutex<Option<MonitorConfig>>,
+    changed: Condvar,
+}
+
+impl Monitor {
+    /// Replaces the config and rescans straight away.
+    pub fn configure(&self, mut config: MonitorConfig) {
+        config.interval_ms = config.interval_ms.max(MIN_INTERVAL_MS);
+        *self.config.lock().unwrap() = Some(config);
+        self.changed.notify_all();
+    }
+
+    /// Blocks until there is a config, or until `interval_ms` has passed since
+    /// the last scan or the config changes, and returns the config to scan with.
+    fn next_scan(&self, scanned: bool) -> MonitorConfig {
+        let mut config = self.config.lock().unwrap();
+        if scanned {
+            if let Some(interval_ms) = config.as_ref().map(|c| c.interval_ms) {
+                config = self
+                    .changed
+                    .wait_timeout(config, Duration::from_millis(interval_ms))
+                    .unwrap()
+                    .0;
+            }
+        }
+        loop {
+            if let Some(current) = config.as_ref() {
+                return current.clone();
+            }
+            config = self.changed.wait(config).unwrap();
+        }
+    }
+}
+
+/// A process (pid plus start time, so a reused pid counts as new) listening on
+/// a port.
+type ListenKey = (u32, Option<u64>, u16, Protocol);
+
+/// When each process was first seen listening on each port, as opposed to
+/// when the process started. Every scan updates it.
+#[derive(Default)]
+pub struct ListenTimes {
+    state: Mutex<ListenTimesState>,
+}
+
+#[derive(Default)]
+struct ListenTimesState {
+    /// First sighting and whether the port was outside the scan before it.
+    first_seen: HashMap<ListenKey, (u64, bool)>,
+    /// The ranges of the previous scan, empty before the first.
+    scanned: Vec<PortRange>,
+}
+
+impl ListenTimes {
+    /// Fills in `listening_seconds_ago` on every process in `listeners`, and
+    /// forgets processes that stopped listening within `ranges`.
+    pub fn observe(&self, listeners: &mut [ListenerInfo], ranges: &[PortRange], now: u64) {
+        let mut state = self.state.lock().unwrap();
+        let scanned = std::mem::replace(&mut state.scanned, ranges.to_vec());
+
+        let mut current = HashSet::new();
+        for listener in listeners.iter_mut() {
+            for process in &mut listener.processes {
+                let key = (
+                    process.pid,
+                    process.identity.start_time,
+                    listener.port,
+                    listener.protocol,
+                );
+                // A port that wasn't being watched may have been listening for
+                // a long time already.
+                let (since, before_launch) = *state
+                    .first_seen
+                    .entry(key)
+                    .or_insert_with(|| (now, !in_any_range(listener.port, &scanned)));
+                process.listening_seconds_ago = Some(now.saturating_sub(since));
+                process.listening_before_launch = before_launch;
+                current.insert(key);
+            }
+        }
+        state
+            .first_seen
+            .retain(|key, _| !in_any_range(key.2, ranges) || current.contains(key));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenerEvent {
+    Added(ListenerInfo),
+    Removed(ListenerInfo),
+    Changed(ListenerInfo),
+}
+
+impl ListenerEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            ListenerEvent::Added(_) => "listener-added",
+            ListenerEvent::Removed(_) => "listener-removed",
+            ListenerEvent::Changed(_) => "listener-changed",
+        }
+    }
+
+    fn listener(&self) -> &ListenerInfo {
+        match self {
+            ListenerEvent::Added(listener)
+            | ListenerEvent::Removed(listener)
+            | ListenerEvent::Changed(listener) => listener,
+        }
+    }
+}
+
+type Snapshot = BTreeMap<(u16, Protocol), ListenerInfo>;
+
+/// Compares two scans by port/protocol. Run and listen times tick on every
+/// scan and connections come and go, so they alone don't make a listener
+/// "changed".
+fn diff(previous: &Snapshot, current: &Snapshot) -> Vec<ListenerEvent> {
+    let mut events = Vec::new();
+    for (key, listener) in current {
+        match previous.get(key) {
+            None => events.push(ListenerEvent::Added(listener.clone())),
+            Some(before) if !same_ignoring_run_time(before, listener) => {
+                events.push(ListenerEvent::Changed(listener.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, listener) in previous {
+        if !current.contains_key(key) {
+            events.push(ListenerEvent::Removed(listener.clone()));
+        }
+    }
+    events
+}
+
+fn same_ignoring_run_time(a: &ListenerInfo, b: &ListenerInfo) -> bool {
+    let without_run_time = |listener: &ListenerInfo| {
+        let mut listener = listener.clone();
+        listener.connections = ConnectionSummary::default();
+        for process in &mut listener.processes {
+            process.started_seconds_ago = None;
+            process.listening_seconds_ago = None;
+        }
+        listener
+    };
+    without_run_time(a) == without_run_time(b)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ConnectionsChanged {
+    port: u16,
+    protocol: Protocol,
+    connections: ConnectionSummary,
+}
+
+/// The connection counts in `current` that differ from the ones last sent,
+/// which `sent` is updated to.
+fn connection_changes(
+    sent: &mut HashMap<(u16, Protocol), ConnectionSummary>,
+    current: &Snapshot,
+) -> Vec<ConnectionsChanged> {
+    sent.retain(|key, _| current.contains_key(key));
+    let mut changes = Vec::new();
+    for (&(port, protocol), listener) in current {
+        if sent.get(&(port, protocol)) != Some(&listener.connections) {
+            sent.insert((port, protocol), listener.connections.clone());
+            changes.push(ConnectionsChanged {
+                port,
+                protocol,
+                connections: listener.connections.clone(),
+            });
+        }
+    }
+    changes
+}
+
+#[derive(Clone, Serialize)]
+struct ScanCompleted<'a> {
+    scan_duration_ms: f64,
+    unavailable_runtimes: &'a [UnavailableRuntime],
+    socket_fallback: Option<&'a str>,
+}
+
+/// Starts the background thread that rescans on the configured interval and
+/// emits `listener-added`, `listener-removed` and `listener-changed`, whether
+/// or not the window is visible, plus `scan-completed` with each scan's
+/// duration in milliseconds and the container runtimes that didn't answer.
+/// Connection counts that moved arrive as `listener-connections`, at most once
+/// every `CONNECTIONS_INTERVAL`, and a scan or history write that fails emits
+/// `monitor-error` with the reason.
+pub fn spawn(app: AppHandle) {
+    thread::spawn(move || {
+        let monitor = app.state::<Monitor>();
+        let mut previous = Snapshot::new();
+        let mut scanned = false;
+        let mut connections_sent = HashMap::new();
+        let mut connections_sent_at: Option<Instant> = None;
+        loop {
+            let config = monitor.next_scan(scanned);
+            scanned = true;
+            let scan = match scan_ports(config.ranges, app.state(), app.state(), app.state()) {
+                Ok(scan) => scan,
+                Err(e) => {
+                    let _ = app.emit("monitor-error", format!("Scan failed: {e}"));
+                    continue;
+                }
+            };
+            let _ = app.emit(
+                "scan-completed",
+                ScanCompleted {
+                    scan_duration_ms: scan.scan_duration_ms,
+                    unavailable_runtimes: &scan.unavailable_runtimes,
+                    socket_fallback: scan.socket_fallback.as_deref(),
+                },
+            );
+            let current: Snapshot = scan
+                .listeners
+                .into_iter()
+                .map(|listener| ((listener.port, listener.protocol), listener))
+                .collect();
+
+            let events = diff(&previous, &current);
+            for event in &events {
+                let _ = app.emit(event.name(), event.listener());
+                if let ListenerEvent::Added(listener) | ListenerEvent::Changed(listener) = event {
+                    connections_sent.insert(
+                        (listener.port, listener.protocol),
+                        listener.connections.clone(),
+                    );
+                }
+            }
+            if connections_sent_at.is_none_or(|at| at.elapsed() >= CONNECTIONS_INTERVAL) {
+                connections_sent_at = Some(Instant::now());
+                for change in connection_changes(&mut connections_sent, &current) {
+                    let _ = app.emit("listener-connections", change);
+                }
+            }
+            if let Err(e) = app.state::<History>().record(&events, crate::unix_now()) {
+                let _ = app.emit(
+                    "monitor-error",
+                    format!("Could not record listener history: {e}"),
+                );
+            }
+            if let Some(tray) = app.tray_by_id(crate::TRAY_ID) {
+                let _ =
+                    tray.set_tooltip(Some(format!("Port-o-Potty: {} listening", current.len())));
+            }
+            previous = current;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+
+    fn listener(port: u16, pid: u32, started_seconds_ago: u64) -> ListenerInfo {
+        let mut listener =
+            ListenerInfo::new(port, Protocol::Tcp, "127.0.0.1".parse::<IpAddr>().unwrap());
+        listener.add_process(crate::ListenerProcess {
+            pid,
+            started_seconds_ago: Some(started_seconds_ago),
+            ..Default::default()
+        });
+        listener
+    }
+
+    fn snapshot(listeners: &[ListenerInfo]) -> Snapshot {
+        listeners
+            .iter()
+            .map(|l| ((l.port, l.protocol), l.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn tracks_listen_time_separately_from_process_run_time() {
+        let times = ListenTimes::default();
+        let narrow = [PortRange {
+            start: 3000,
+            end: 9000,
+        }];
+        let wide = [PortRange {
+            start: 1000,
+            end: 9000,
+        }];
+        let listen_within = |ranges: &[PortRange], listeners: &[ListenerInfo], now| {
+            let mut listeners = listeners.to_vec();
+            times.observe(&mut listeners, ranges, now);
+            listeners
+                .iter()
+                .map(|l| {
+                    let p = &l.processes[0];
+                    (l.port, p.listening_seconds_ago, p.listening_before_launch)
+                })
+                .collect::<Vec<_>>()
+        };
+        let listen = |listeners: &[ListenerInfo], now| listen_within(&narrow, listeners, now);
+
+        // A day-old JVM that was already listening when we started.
+        assert_eq!(
+            listen(&[listener(8080, 10, 86_400)], 1_000),
+            [(8080, Some(0), true)]
+        );
+        // It opens a second port a minute later.
+        assert_eq!(
+            listen(
+                &[listener(8080, 10, 86_460), listener(8081, 10, 86_460)],
+                1_060
+            ),
+            [(8080, Some(60), true), (8081, Some(0), false)]
+        );
+        // The port closes and reopens: the listen time starts over.
+        listen(&[listener(8080, 10, 86_470)], 1_070);
+        assert_eq!(
+            listen(
+                &[listener(8080, 10, 86_480), listener(8081, 10, 86_480)],
+                1_080
+            ),
+            [(8080, Some(80), true), (8081, Some(0), false)]
+        );
+        // Widening the ranges finds a long-lived daemon nobody was watching.
+        assert_eq!(
+            listen_within(
+                &wide,
+                &[listener(2049, 5, 90_000), listener(8080, 10, 86_490)],
+                1_090
+            ),
+            [(2049, Some(0), true), (8080, Some(90), true)]
+        );
+    }
+
+    fn busy(mut listener: ListenerInfo) -> ListenerInfo {
+        listener.connections.established = 12;
+        listener
+    }
+
+    #[test]
+    fn diffs_scans_into_added_removed_and_changed_events() {
+        let previous = snapshot(&[listener(3000, 10, 60), listener(5432, 20, 60)]);
+        let current = snapshot(&[
+            // Only the run time and connections moved on.
+            busy(listener(3000, 10, 65)),
+            // Restarted under a new pid.
+            listener(5432, 21, 1),
+            listener(8080, 30, 1),
+        ]);
+
+        let events: Vec<(&str, u16)> = diff(&previous, &current)
+            .iter()
+            .map(|event| (event.name(), event.listener().port))
+            .collect();
+        assert_eq!(
+            events,
+            [("listener-changed", 5432), ("listener-added", 8080)]
+        );
+        assert_eq!(
+            diff(&current, &Snapshot::new())
+                .iter()
+                .map(ListenerEvent::name)
+                .collect::<Vec<_>>(),
+            ["listener-removed"; 3]
+        );
+    }
+
+    #[test]
+    fn sends_connection_counts_only_when_they_move() {
+        let mut sent = HashMap::new();
+        let quiet = snapshot(&[listener(3000, 10, 60), listener(8080, 30, 60)]);
+        assert_eq!(connection_changes(&mut sent, &quiet).len(), 2);
+        assert!(connection_changes(&mut sent, &quiet).is_empty());
+
+        let busier = snapshot(&[busy(listener(3000, 10, 65)), listener(8080, 30, 65)]);
+        let changes = connection_changes(&mut sent, &busier);
+        assert_eq!(
+            changes
+                .iter()
+                .map(|c| (c.port, c.connections.established))
+                .collect::<Vec<_>>(),
+            [(3000, 12)]
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! Linux socket enumeration over NETLINK_SOCK_DIAG, the interface `ss` uses.
+//! The kernel filters by TCP state, so finding listeners doesn't mean reading
+//! every connection on the machine, and owners are resolved from /proc only
+//! for the sockets that came back.
+
+use crate::{Protocol, Socket, SocketQueue};
+use netstat2::{ProtocolSocketInfo, SocketInfo, TcpSocketInfo, TcpState, UdpSocketInfo};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    ptr,
+};
+
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+const TCP_LISTEN: u8 = 10;
+
+/// Bit masks of kernel TCP states (`1 << TCP_*`) for `sockets`.
+pub const LISTEN: u32 = 1 << TCP_LISTEN;
+/// ESTABLISHED, TIME_WAIT and CLOSE_WAIT: the connections a listener reports.
+pub const CONNECTIONS: u32 = 1 << 1 | 1 << 6 | 1 << 8;
+const ALL_STATES: u32 = !0;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InetDiagSockId {
+    sport: [u8; 2],
+    dport: [u8; 2],
+    src: [u8; 16],
+    dst: [u8; 16],
+    interface: u32,
+    cookie: [u32; 2],
+}
+
+#[repr(C)]
+struct InetDiagReqV2 {
+    family: u8,
+    protocol: u8,
+    ext: u8,
+    pad: u8,
+    states: u32,
+    id: InetDiagSockId,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InetDiagMsg {
+    family: u8,
+    state: u8,
+    timer: u8,
+    retrans: u8,
+    id: InetDiagSockId,
+    expires: u32,
+    rqueue: u32,
+    wqueue: u32,
+    uid: u32,
+    inode: u32,
+}
+
+#[repr(C)]
+struct Request {
+    header: libc::nlmsghdr,
+    body: InetDiagReqV2,
+}
+
+/// Sockets of `protocol` in any of `tcp_states` (every unconnected UDP
+/// socket is included), with their queues. Owners are left to
+/// `resolve_owners`, so several protocols share one /proc walk.
+pub fn sockets(protocol: Protocol, tcp_states: u32) -> io::Result<Vec<Socket>> {
+    let states = match protocol {
+        Protocol::Tcp => tcp_states,
+        Protocol::Udp => ALL_STATES,
+    };
+    let mut sockets = Vec::new();
+    for family in [libc::AF_INET, libc::AF_INET6] {
+        for msg in dump(family, protocol, states)? {
+            if !is_udp_client(protocol, &msg) {
+                sockets.push(Socket {
+                    info: socket_info(protocol, &msg),
+                    queue: Some(SocketQueue {
+                        received: msg.rqueue,
+                        sent: msg.wqueue,
+                    }),
+                });
+            }
+        }
+    }
+    Ok(sockets)
+}
+
+/// Fills in the pids holding each socket.
+pub fn resolve_owners(sockets: &mut [Socket]) {
+    let inodes: HashSet<u32> = sockets
+        .iter()
+        .map(|s| s.info.inode)
+        .filter(|&i| i != 0)
+        .collect();
+    let mut owners = owners_by_inode(&inodes);
+    for socket in sockets {
+        socket.info.associated_pids = owners.remove(&socket.info.inode).unwrap_or_default();
+    }
+}
+
+/// Whether anything is bound to `port`: a listening TCP socket, or an
+/// unconnected UDP socket. Skips the /proc walk, since owners don't matter
+/// here.
+pub fn has_listener(port: u16, protocol: Protocol) -> io::Result<bool> {
+    let states = match protocol {
+        Protocol::Tcp => LISTEN,
+        Protocol::Udp => ALL_STATES,
+    };
+    for family in [libc::AF_INET, libc::AF_INET6] {
+        if dump(family, protocol, states)?
+            .iter()
+            .any(|msg| u16::from_be_bytes(msg.id.sport) == port && !is_udp_client(protocol, msg))
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// A UDP socket connected to a peer, such as a browser's QUIC connection or a
+/// resolver's query, is a client rather than a listener.
+fn is_udp_client(protocol: Protocol, msg: &InetDiagMsg) -> bool {
+    protocol == Protocol::Udp && msg.id.dport != [0, 0]
+}
+
+fn dump(family: libc::c_int, protocol: Protocol, states: u32) -> io::Result<Vec<InetDiagMsg>> {
+    // SAFETY: socket(2) takes no pointers; the result is checked below.
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_SOCK_DIAG,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just returned by socket(2) and nothing else owns it.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let request = Request {
+        header: libc::nlmsghdr {
+            nlmsg_len: mem::size_of::<Request>() as u32,
+            nlmsg_type: SOCK_DIAG_BY_FAMILY,
+            nlmsg_flags: (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16,
+            nlmsg_seq: 1,
+            nlmsg_pid: 0,
+        },
+        body: InetDiagReqV2 {
+            family: family as u8,
+            protocol: match protocol {
+                Protocol::Tcp => libc::IPPROTO_TCP as u8,
+                Protocol::Udp => libc::IPPROTO_UDP as u8,
+            },
+            ext: 0,
+            pad: 0,
+            states,
+            // SAFETY: the socket id is plain integers and byte arrays, for
+            // which all zeroes is valid (and means "any").
+            id: unsafe { mem::zeroed() },
+        },
+    };
+    // SAFETY: sockaddr_nl is plain integers; all zeroes addresses the kernel.
+    let mut kernel: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    // SAFETY: both pointers are to live locals, passed with their own sizes.
+    let sent = unsafe {
+        libc::sendto(
+            fd.as_raw_fd(),
+            ptr::addr_of!(request).cast(),
+            mem::size_of::<Request>(),
+            0,
+            ptr::addr_of!(kernel).cast(),
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut messages = Vec::new();
+    let mut buf = vec![0u8; 32 * 1024];
+    loop {
+        // SAFETY: the kernel writes at most `buf.len()` bytes into `buf`.
+        let len = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if len < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(error);
+        }
+        if parse_messages(&buf[..len as usize], &mut messages)? {
+            return Ok(messages);
+        }
+    }
+}
+
+/// Appends the sock_diag replies in one datagram to `out`, returning whether
+/// the dump is complete.
+fn parse_messages(mut data: &[u8], out: &mut Vec<InetDiagMsg>) -> io::Result<bool> {
+    let header_len = mem::size_of::<libc::nlmsghdr>();
+    while data.len() >= header_len {
+        // SAFETY: the loop condition leaves at least a header's worth of
+        // bytes, and nlmsghdr is plain integers, valid for any bytes.
+        let header: libc::nlmsghdr = unsafe { ptr::read_unaligned(data.as_ptr().cast()) };
+        let len = header.nlmsg_len as usize;
+        if len < header_len || len > data.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated netlink message",
+            ));
+        }
+        let payload = &data[header_len..len];
+        match i32::from(header.nlmsg_type) {
+            libc::NLMSG_DONE => return Ok(true),
+            libc::NLMSG_ERROR => {
+                let errno = if payload.len() >= 4 {
+                    -i32::from_ne_bytes([payload[0], payload[1], payload[2], payload[3]])
+                } else {
+                    libc::EINVAL
+                };
+                return Err(io::Error::from_raw_os_error(errno));
+            }
+            _ if payload.len() >= mem::size_of::<InetDiagMsg>() => {
+                // SAFETY: the guard checked the length, and InetDiagMsg is
+                // plain integers and byte arrays, valid for any bytes.
+                out.push(unsafe { ptr::read_unaligned(payload.as_ptr().cast()) });
+            }
+            _ => {}
+        }
+        // Messages are padded to 4 bytes.
+        data = &data[((len + 3) & !3).min(data.len())..];
+    }
+    Ok(false)
+}
+
+fn socket_info(protocol: Protocol, msg: &InetDiagMsg) -> SocketInfo {
+    let local_addr = address(msg.family, &msg.id.src);
+    let local_port = u16::from_be_bytes(msg.id.sport);
+    let protocol_socket_info = match protocol {
+        Protocol::Tcp => ProtocolSocketInfo::Tcp(TcpSocketInfo {
+            local_addr,
+            local_port,
+            remote_addr: address(msg.family, &msg.id.dst),
+            remote_port: u16::from_be_bytes(msg.id.dport),
+            state: tcp_state(msg.state),
+        }),
+        Protocol::Udp => ProtocolSocketInfo::Udp(UdpSocketInfo {
+            local_addr,
+            local_port,
+        }),
+    };
+    SocketInfo {
+        protocol_socket_info,
+        associated_pids: Vec::new(),
+        inode: msg.inode,
+        uid: msg.uid,
+    }
+}
+
+fn address(family: u8, bytes: &[u8; 16]) -> IpAddr {
+    if i32::from(family) == libc::AF_INET {
+        IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(*bytes))
+    }
+}
+
+fn tcp_state(state: u8) -> TcpState {
+    match state {
+        1 => TcpState::Established,
+        2 => TcpState::SynSent,
+        3 | 12 => TcpState::SynReceived,
+        4 => TcpState::FinWait1,
+        5 => TcpState::FinWait2,
+        6 => TcpState::TimeWait,
+        7 => TcpState::Closed,
+        8 => TcpState::CloseWait,
+        9 => TcpState::LastAck,
+        TCP_LISTEN => TcpState::Listen,
+        11 => TcpState::Closing,
+        _ => TcpState::Unknown,
+    }
+}
+
+/// Maps socket inodes to the pids holding them by reading the
+/// `socket:[inode]` links under /proc/<pid>/fd. Processes we may not inspect
+/// are skipped, as `ss -p` does.
+fn owners_by_inode(inodes: &HashSet<u32>) -> HashMap<u32, Vec<u32>> {
+    let mut owners: HashMap<u32, Vec<u32>> = HashMap::new();
+    if inodes.is_empty() {
+        return owners;
+    }
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return owners;
+    };
+    for entry in procs.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Some(inode) = fs::read_link(fd.path())
+                .ok()
+                .and_then(|target| socket_inode(target.to_str()?))
+            else {
+                continue;
+            };
+            if inodes.contains(&inode) {
+                let pids = owners.entry(inode).or_default();
+                if !pids.contains(&pid) {
+                    pids.push(pid);
+                }
+            }
+        }
+    }
+    owners
+}
+
+fn socket_inode(link: &str) -> Option<u32> {
+    link.strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream, UdpSocket};
+
+    #[test]
+    fn finds_our_own_listeners_and_their_pid() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let udp = UdpSocket::bind("[::1]:0").unwrap();
+        let tcp_port = tcp.local_addr().unwrap().port();
+        let udp_port = udp.local_addr().unwrap().port();
+
+        assert!(has_listener(tcp_port, Protocol::Tcp).unwrap());
+        assert!(has_listener(udp_port, Protocol::Udp).unwrap());
+
+        let mut sockets = sockets(Protocol::Tcp, LISTEN).unwrap();
+        sockets.extend(super::sockets(Protocol::Udp, LISTEN).unwrap());
+        resolve_owners(&mut sockets);
+        let owner = |port: u16| {
+            sockets
+                .iter()
+                .find(|s| s.info.local_port() == port)
+                .map(|s| s.info.associated_pids.clone())
+        };
+        assert_eq!(owner(tcp_port), Some(vec![std::process::id()]));
+        assert_eq!(owner(udp_port), Some(vec![std::process::id()]));
+
+        drop(tcp);
+        assert!(!has_listener(tcp_port, Protocol::Tcp).unwrap());
+    }
+
+    #[test]
+    fn leaves_out_connected_udp_sockets() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.connect(server.local_addr().unwrap()).unwrap();
+        let client_port = client.local_addr().unwrap().port();
+
+        assert!(has_listener(server.local_addr().unwrap().port(), Protocol::Udp).unwrap());
+        assert!(!has_listener(client_port, Protocol::Udp).unwrap());
+        assert!(!sockets(Protocol::Udp, 0)
+            .unwrap()
+            .iter()
+            .any(|s| s.info.local_port() == client_port));
+    }
+
+    #[test]
+    fn reports_the_accept_backlog_of_a_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let _waiting = TcpStream::connect(("127.0.0.1", port)).unwrap();
+
+        let queue = sockets(Protocol::Tcp, LISTEN)
+            .unwrap()
+            .into_iter()
+            .find(|s| s.info.local_port() == port)
+            .and_then(|s| s.queue)
+            .unwrap();
+        assert_eq!(queue.received, 1);
+        assert!(queue.sent >= 1);
+    }
+
+    #[test]
+    fn parses_socket_fd_links() {
+        assert_eq!(socket_inode("socket:[91822]"), Some(91_822));
+        assert_eq!(socket_inode("pipe:[91822]"), None);
+        assert_eq!(socket_inode("/dev/null"), None);
+    }
+}
@@ -0,0 +1,113 @@
+use std::{collections::HashSet, sync::Mutex};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind, Users};
+
+/// The process table behind every scan, kept between scans so a scan
+/// only refreshes the handful of pids bound to ports in range instead of
+/// building a whole new `System`.
+pub struct ProcessCache {
+    tables: Mutex<Tables>,
+}
+
+struct Tables {
+    system: System,
+    users: Users,
+    /// Pids in `system`, so those that exit can be dropped.
+    tracked: HashSet<Pid>,
+}
+
+impl Default for ProcessCache {
+    fn default() -> Self {
+        Self {
+            tables: Mutex::new(Tables {
+                system: System::new(),
+                users: Users::new_with_refreshed_list(),
+                tracked: HashSet::new(),
+            }),
+        }
+    }
+}
+
+impl ProcessCache {
+    /// Refreshes `pids`, forgets tracked processes that have since exited, and
+    /// hands the table to `f`. Other processes are not looked at.
+    pub fn with_processes<R>(
+        &self,
+        pids: &HashSet<u32>,
+        f: impl FnOnce(&System, &Users) -> R,
+    ) -> R {
+        let mut tables = self.tables.lock().unwrap();
+        let Tables {
+            system,
+            users,
+            tracked,
+        } = &mut *tables;
+
+        let wanted: Vec<Pid> = pids.iter().map(|&pid| Pid::from_u32(pid)).collect();
+        // Name, start time and parent come with every refresh; a pid that was
+        // reused is replaced rather than updated.
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&wanted),
+            true,
+            ProcessRefreshKind::nothing()
+                .with_cmd(UpdateKind::OnlyIfNotSet)
+                .with_exe(UpdateKind::OnlyIfNotSet)
+                .with_cwd(UpdateKind::Always)
+                .with_user(UpdateKind::Always),
+        );
+        let stale: Vec<Pid> = tracked
+            .iter()
+            .filter(|pid| !pids.contains(&pid.as_u32()))
+            .copied()
+            .collect();
+        if !stale.is_empty() {
+            system.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&stale),
+                true,
+                ProcessRefreshKind::nothing(),
+            );
+        }
+        tracked.clear();
+        tracked.extend(system.processes().keys().copied());
+
+        let unknown_user = system
+            .processes()
+            .values()
+            .filter_map(|process| process.user_id())
+            .any(|uid| users.get_user_by_id(uid).is_none());
+        if unknown_user {
+            users.refresh();
+        }
+
+        f(system, users)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn refreshes_only_the_requested_pids_and_forgets_exited_ones() {
+        let cache = ProcessCache::default();
+        let ours = std::process::id();
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let loaded = |pids: &[u32]| {
+            cache.with_processes(&pids.iter().copied().collect(), |system, _| {
+                let mut loaded: Vec<u32> = system.processes().keys().map(|p| p.as_u32()).collect();
+                loaded.sort_unstable();
+                loaded
+            })
+        };
+
+        let mut both = vec![ours, child.id()];
+        both.sort_unstable();
+        assert_eq!(loaded(&both), both);
+        // Still alive, so kept even though it wasn't asked for.
+        assert_eq!(loaded(&[ours]), both);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(loaded(&[ours]), [ours]);
+    }
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Files that mark the root of a project, checked nearest-first while walking
+/// up from a process's working directory.
+const PROJECT_MARKERS: [&str; 5] = [
+    "package.json",
+    "Cargo.toml",
+    "pyproject.toml",
+    "go.mod",
+    ".git",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProjectInfo {
+    pub name: String,
+    pub root: String,
+    pub git_branch: Option<String>,
+}
+
+pub fn detect_project(cwd: &Path) -> Option<ProjectInfo> {
+    let root = cwd.ancestors().find(|dir| {
+        PROJECT_MARKERS
+            .iter()
+            .any(|marker| dir.join(marker).exists())
+    })?;
+    let name = manifest_name(root).unwrap_or_else(|| dir_name(root));
+    let git_branch = root
+        .ancestors()
+        .find_map(git_dir)
+        .and_then(|git_dir| fs::read_to_string(git_dir.join("HEAD")).ok())
+        .and_then(|head| branch_from_head(&head));
+
+    Some(ProjectInfo {
+        name,
+        root: root.to_string_lossy().to_string(),
+        git_branch,
+    })
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| dir.to_string_lossy().to_string())
+}
+
+fn manifest_name(root: &Path) -> Option<String> {
+    if let Ok(package_json) = fs::read_to_string(root.join("package.json")) {
+        let name = serde_json::from_str::<serde_json::Value>(&package_json)
+            .ok()
+            .and_then(|value| value.get("name")?.as_str().map(str::to_string));
+        if name.is_some() {
+            return name;
+        }
+    }
+    if let Ok(cargo_toml) = fs::read_to_string(root.join("Cargo.toml")) {
+        if let Some(name) = toml_section_name(&cargo_toml, &["package"]) {
+            return Some(name);
+        }
+    }
+    if let Ok(pyproject) = fs::read_to_string(root.join("pyproject.toml")) {
+        if let Some(name) = toml_section_name(&pyproject, &["project", "tool.poetry"]) {
+            return Some(name);
+        }
+    }
+    if let Ok(go_mod) = fs::read_to_string(root.join("go.mod")) {
+        return go_mod
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("module "))
+            .map(|module| module.trim().trim_matches('"').to_string());
+    }
+    None
+}
+
+/// Reads `name = "..."` from one of the given TOML tables. Good enough for
+/// the manifests we look at without pulling in a full TOML parser.
+fn toml_section_name(contents: &str, sections: &[&str]) -> Option<String> {
+    let mut in_section = false;
+    for line in contents.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_section = sections.contains(&header.trim());
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if key.trim() == "name" {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            if !value.is_empty() {
+                return Some(value.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Resolves the git directory for `dir`, following the `gitdir:` pointer that
+/// worktrees and submodules leave in a `.git` file.
+fn git_dir(dir: &Path) -> Option<PathBuf> {
+    let dot_git = dir.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let pointer = fs::read_to_string(&dot_git).ok()?;
+    let target = pointer.trim().strip_prefix("gitdir:")?.trim();
+    Some(dir.join(target))
+}
+
+fn branch_from_head(head: &str) -> Option<String> {
+    let head = head.trim();
+    if let Some(reference) = head.strip_prefix("ref:") {
+        let reference = reference.trim();
+        return Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        );
+    }
+    // Detached HEAD: show the abbreviated commit like `git status` does.
+    head.get(..7).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_branch_from_symbolic_and_detached_head() {
+        assert_eq!(
+            branch_from_head("ref: refs/heads/feature/login\n"),
+            Some("feature/login".to_string())
+        );
+        assert_eq!(
+            branch_from_head("4f2a9c1d0e8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f\n"),
+            Some("4f2a9c1".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_name_from_the_requested_toml_table() {
+        let cargo = "[workspace]\nname = \"nope\"\n\n[package]\nname = \"port-o-potty\"\nversion = \"0.1.0\"\n";
+        let poetry = "[build-system]\nrequires = []\n[tool.poetry]\nname = 'api'\n";
+
+        assert_eq!(
+            toml_section_name(cargo, &["package"]),
+            Some("port-o-potty".to_string())
+        );
+        assert_eq!(
+            toml_section_name(poetry, &["project", "tool.poetry"]),
+            Some("api".to_string())
+        );
+    }
+
+    #[test]
+    fn finds_worktree_root_name_and_branch_from_nested_cwd() {
+        let base =
+            std::env::temp_dir().join(format!("port-o-potty-project-{}", std::process::id()));
+        let worktree = base.join("checkouts/web-feature");
+        let git_meta = base.join("repo.git/worktrees/web-feature");
+        fs::create_dir_all(worktree.join("src/server")).unwrap();
+        fs::create_dir_all(&git_meta).unwrap();
+        fs::write(worktree.join("package.json"), r#"{"name":"web"}"#).unwrap();
+        fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", git_meta.display()),
+        )
+        .unwrap();
+        fs::write(git_meta.join("HEAD"), "ref: refs/heads/feature\n").unwrap();
+
+        let project = detect_project(&worktree.join("src/server"));
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(
+            project,
+            Some(ProjectInfo {
+                name: "web".to_string(),
+                root: worktree.to_string_lossy().to_string(),
+                git_branch: Some("feature".to_string()),
+            })
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Mutex};
+
+/// Linux keeps only the first 15 bytes of a process name (`comm`), so
+/// systemd-resolved shows up as "systemd-resolve".
+const COMM_LEN: usize = 15;
+
+/// Process names that are never killed: init systems, remote access, name
+/// resolution and container daemons whose loss takes other things down with
+/// them.
+const BUILTIN_PROTECTED_NAMES: [&str; 14] = [
+    "init",
+    "systemd",
+    "systemd-resolved",
+    "systemd-networkd",
+    "launchd",
+    "sshd",
+    "dockerd",
+    "containerd",
+    "com.docker.backend",
+    "com.docker.vpnkit",
+    "kernel_task",
+    "WindowServer",
+    "loginwindow",
+    "mDNSResponder",
+];
+
+/// One entry of the protected list. User entries arrive from the UI as
+/// `{ "kind": "name", "value": "postgres" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum ProtectedMatcher {
+    Pid(u32),
+    Name(String),
+    /// An exact executable path, or every executable under a directory when
+    /// the value ends with `/`.
+    ExePath(String),
+    User(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProcessFacts {
+    pub pid: u32,
+    pub name: Option<String>,
+    pub exe_path: Option<String>,
+    pub user: Option<String>,
+}
+
+impl ProtectedMatcher {
+    fn matches(&self, facts: &ProcessFacts) -> bool {
+        match self {
+            ProtectedMatcher::Pid(pid) => *pid == facts.pid,
+            ProtectedMatcher::Name(name) => {
+                let name = name.trim();
+                let exe_name = facts
+                    .exe_path
+                    .as_deref()
+                    .and_then(|path| Path::new(path).file_name()?.to_str());
+                facts
+                    .name
+                    .as_deref()
+                    .is_some_and(|actual| same_name(actual, name))
+                    || exe_name.is_some_and(|actual| actual.eq_ignore_ascii_case(name))
+            }
+            ProtectedMatcher::ExePath(path) => facts.exe_path.as_deref().is_some_and(|actual| {
+                if path.ends_with('/') {
+                    actual.starts_with(path.as_str())
+                } else {
+                    actual == path
+                }
+            }),
+            ProtectedMatcher::User(user) => facts.user.as_deref() == Some(user.trim()),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            ProtectedMatcher::Pid(pid) if *pid == std::process::id() => {
+                "it is Port-o-Potty itself".to_string()
+            }
+            ProtectedMatcher::Pid(pid) => format!("PID {pid} is protected"),
+            ProtectedMatcher::Name(name) => format!("{name} processes are protected"),
+            ProtectedMatcher::ExePath(path) => format!("{path} is protected"),
+            ProtectedMatcher::User(user) => format!("processes owned by {user} are protected"),
+        }
+    }
+}
+
+/// Whether process name `actual` is `wanted`, allowing for Linux cutting it
+/// short.
+fn same_name(actual: &str, wanted: &str) -> bool {
+    actual.eq_ignore_ascii_case(wanted)
+        || (cfg!(target_os = "linux")
+            && actual.len() == COMM_LEN
+            && wanted
+                .get(..COMM_LEN)
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(actual)))
+}
+
+fn builtin_matchers() -> Vec<ProtectedMatcher> {
+    let mut matchers = vec![
+        ProtectedMatcher::Pid(1),
+        ProtectedMatcher::Pid(std::process::id()),
+    ];
+    matchers.extend(
+        BUILTIN_PROTECTED_NAMES
+            .iter()
+            .map(|name| ProtectedMatcher::Name(name.to_string())),
+    );
+    matchers
+}
+
+/// Explains why `facts` must not be killed, checking the built-in list first
+/// and then the user's entries.
+pub fn protection_reason(facts: &ProcessFacts, user_list: &[ProtectedMatcher]) -> Option<String> {
+    builtin_matchers()
+        .iter()
+        .chain(user_list)
+        .find(|matcher| matcher.matches(facts))
+        .map(ProtectedMatcher::describe)
+}
+
+/// Explains why a process inside a container must not be stopped. Only the
+/// user's entries apply: the built-in ones guard the host, and a container's
+/// own init or sshd goes down with it.
+pub fn user_protection_reason(
+    facts: &ProcessFacts,
+    user_list: &[ProtectedMatcher],
+) -> Option<String> {
+    user_list
+        .iter()
+        .find(|matcher| matcher.matches(facts))
+        .map(ProtectedMatcher::describe)
+}
+
+/// The user's protected list, held by the backend so every kill path checks
+/// it whoever the caller is; the UI replaces it through
+/// `set_protected_processes`.
+#[derive(Default)]
+pub struct ProtectedList(Mutex<Vec<ProtectedMatcher>>);
+
+impl ProtectedList {
+    pub fn set(&self, entries: Vec<ProtectedMatcher>) {
+        *self.0.lock().unwrap() = entries;
+    }
+
+    pub fn entries(&self) -> Vec<ProtectedMatcher> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts(pid: u32, name: &str, exe_path: &str, user: &str) -> ProcessFacts {
+        ProcessFacts {
+            pid,
+            name: Some(name.to_string()),
+            exe_path: Some(exe_path.to_string()),
+            user: Some(user.to_string()),
+        }
+    }
+
+    #[test]
+    fn protects_builtin_and_user_listed_processes() {
+        let user_list: Vec<ProtectedMatcher> = serde_json::from_str(
+            r#"[{"kind":"exe_path","value":"/opt/corp/"},{"kind":"user","value":"postgres"}]"#,
+        )
+        .unwrap();
+
+        assert!(protection_reason(&facts(1, "systemd", "/sbin/init", "root"), &[]).is_some());
+        assert!(protection_reason(&facts(812, "SSHD", "/usr/sbin/sshd", "root"), &[]).is_some());
+        // The exe of another user's process is often unreadable, leaving only
+        // the cut-short name.
+        let resolved = ProcessFacts {
+            pid: 640,
+            name: Some("systemd-resolve".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            cfg!(target_os = "linux"),
+            protection_reason(&resolved, &[]).is_some()
+        );
+        assert!(protection_reason(
+            &facts(641, "x", "/usr/lib/systemd/systemd-networkd", "root"),
+            &[]
+        )
+        .is_some());
+        assert!(protection_reason(&facts(642, "systemd-re", "/x", "root"), &[]).is_none());
+        assert_eq!(
+            protection_reason(&facts(std::process::id(), "port-o-potty", "/x", "me"), &[]),
+            Some("it is Port-o-Potty itself".to_string())
+        );
+        assert!(protection_reason(
+            &facts(900, "agent", "/opt/corp/bin/agent", "me"),
+            &user_list
+        )
+        .is_some());
+        assert!(protection_reason(
+            &facts(901, "postgres", "/usr/bin/postgres", "postgres"),
+            &user_list
+        )
+        .is_some());
+        assert!(
+            protection_reason(&facts(902, "node", "/usr/local/bin/node", "me"), &user_list)
+                .is_none()
+        );
+        // Inside a container only the user's entries count.
+        assert!(
+            user_protection_reason(&facts(903, "sshd", "/usr/sbin/sshd", "root"), &[]).is_none()
+        );
+        assert!(user_protection_reason(
+            &facts(
+                904,
+                "postgres",
+                "/usr/lib/postgresql/bin/postgres",
+                "postgres"
+            ),
+            &user_list
+        )
+        .is_some());
+    }
+}
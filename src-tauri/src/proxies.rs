@@ -0,0 +1,214 @@
+//! Userland port forwarders. With Docker's userland proxy on, the process
+//! bound to a published port is a `docker-proxy`, and rootless runtimes
+//! forward through rootlesskit, Podman's rootlessport or slirp4netns. Where
+//! their arguments say which container they forward to, that container is the
+//! listener's real owner.
+
+use crate::runtime::{is_container_id, Container, RuntimeKind};
+use serde::Serialize;
+use std::{net::IpAddr, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyKind {
+    DockerProxy,
+    Rootlesskit,
+    Rootlessport,
+    Slirp4netns,
+}
+
+/// A forwarding process and what its arguments say it forwards to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    pub container_ip: Option<IpAddr>,
+    pub container_port: Option<u16>,
+    /// Podman names the slirp4netns API socket after the container.
+    pub container_id: Option<String>,
+}
+
+/// Recognises a forwarder by its process name or its `argv[0]`.
+pub fn proxy_of(name: &str, command_line: &[String]) -> Option<Proxy> {
+    let program = command_line
+        .first()
+        .and_then(|arg0| Path::new(arg0).file_name()?.to_str());
+    let kind = [Some(name), program]
+        .into_iter()
+        .flatten()
+        .find_map(|name| match name {
+            "docker-proxy" => Some(ProxyKind::DockerProxy),
+            "rootlesskit" => Some(ProxyKind::Rootlesskit),
+            "rootlessport" => Some(ProxyKind::Rootlessport),
+            "slirp4netns" => Some(ProxyKind::Slirp4netns),
+            _ => None,
+        })?;
+    let mut proxy = Proxy {
+        kind,
+        container_ip: None,
+        container_port: None,
+        container_id: None,
+    };
+    match kind {
+        // docker-proxy -proto tcp -host-ip 0.0.0.0 -host-port 8080
+        //   -container-ip 172.17.0.2 -container-port 80
+        ProxyKind::DockerProxy => {
+            proxy.container_ip = flag(command_line, "container-ip").and_then(|ip| ip.parse().ok());
+            proxy.container_port =
+                flag(command_line, "container-port").and_then(|port| port.parse().ok());
+        }
+        // slirp4netns ... --api-socket /run/user/1000/libpod/tmp/<id>.net
+        ProxyKind::Slirp4netns => {
+            proxy.container_id = flag(command_line, "api-socket")
+                .and_then(|socket| {
+                    Path::new(socket)
+                        .file_name()?
+                        .to_str()?
+                        .strip_suffix(".net")
+                })
+                .filter(|id| is_container_id(id))
+                .map(str::to_string);
+        }
+        // Both take their port map over a socket or stdin, so only the
+        // published host port can tell which container they serve.
+        ProxyKind::Rootlesskit | ProxyKind::Rootlessport => {}
+    }
+    Some(proxy)
+}
+
+impl Proxy {
+    /// The running container this forwards to, if its arguments say.
+    pub fn container<'a>(
+        &self,
+        running: &'a [(RuntimeKind, Container)],
+    ) -> Option<&'a (RuntimeKind, Container)> {
+        if let Some(id) = &self.container_id {
+            return running.iter().find(|(_, container)| &container.id == id);
+        }
+        let ip = self.container_ip?;
+        // The port rules out a container on another daemon's network that
+        // happens to have the same address.
+        running.iter().find(|(_, container)| {
+            container.addresses.contains(&ip)
+                && self.container_port.is_none_or(|port| {
+                    container
+                        .ports
+                        .iter()
+                        .any(|binding| binding.container_port == port)
+                })
+        })
+    }
+}
+
+/// The value of a Go-style flag: `-name value`, `-name=value`, or the same
+/// with `--`.
+fn flag<'a>(command_line: &'a [String], name: &str) -> Option<&'a str> {
+    let mut args = command_line.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let Some(given) = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-')) else {
+            continue;
+        };
+        if given == name {
+            return args.next().map(String::as_str);
+        }
+        if let Some(value) = given
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{runtime::PortBinding, Protocol};
+
+    const ID: &str = "3f4e0a1c9b2d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f";
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn reads_where_a_proxy_forwards_to() {
+        let docker_proxy = proxy_of(
+            "docker-proxy",
+            &args("/usr/bin/docker-proxy -proto tcp -host-ip 0.0.0.0 -host-port 8080 -container-ip 172.17.0.2 -container-port 80 -use-listen-fd"),
+        )
+        .unwrap();
+        assert_eq!(docker_proxy.kind, ProxyKind::DockerProxy);
+        assert_eq!(
+            docker_proxy.container_ip,
+            Some("172.17.0.2".parse().unwrap())
+        );
+        assert_eq!(docker_proxy.container_port, Some(80));
+
+        let slirp = proxy_of(
+            "slirp4netns",
+            &args(&format!("/usr/bin/slirp4netns --disable-host-loopback --mtu=65520 -c -e 3 -r 4 --netns-type=path /run/user/1000/netns/netns-1 tap0 --api-socket=/run/user/1000/libpod/tmp/{ID}.net")),
+        )
+        .unwrap();
+        assert_eq!(slirp.container_id.as_deref(), Some(ID));
+
+        let rootlesskit = proxy_of(
+            "rootlesskit",
+            &args(
+                "rootlesskit --net=slirp4netns --port-driver=builtin /usr/bin/dockerd-rootless.sh",
+            ),
+        )
+        .unwrap();
+        assert_eq!(rootlesskit.kind, ProxyKind::Rootlesskit);
+        assert_eq!(rootlesskit.container_ip, None);
+
+        assert_eq!(proxy_of("nginx", &args("nginx -g daemon off;")), None);
+    }
+
+    #[test]
+    fn finds_the_container_a_proxy_forwards_to() {
+        let container = |id: &str, address: &str, port| {
+            (
+                RuntimeKind::Docker,
+                Container {
+                    ports: vec![PortBinding::new(None, None, port, Protocol::Tcp)],
+                    addresses: vec![address.parse().unwrap()],
+                    ..Container::named(id)
+                },
+            )
+        };
+        let running = vec![
+            container("db", "172.17.0.2", 5432),
+            container("web", "172.17.0.2", 80),
+            container(ID, "10.88.0.4", 80),
+        ];
+        let found = |proxy: Option<Proxy>| {
+            proxy
+                .unwrap()
+                .container(&running)
+                .map(|(_, container)| container.id.clone())
+        };
+
+        assert_eq!(
+            found(proxy_of(
+                "docker-proxy",
+                &args("docker-proxy -container-ip 172.17.0.2 -container-port 80")
+            )),
+            Some("web".to_string())
+        );
+        assert_eq!(
+            found(proxy_of(
+                "slirp4netns",
+                &args(&format!("slirp4netns --api-socket /tmp/{ID}.net"))
+            )),
+            Some(ID.to_string())
+        );
+        assert_eq!(
+            found(proxy_of(
+                "docker-proxy",
+                &args("docker-proxy -container-ip 172.17.0.9 -container-port 80")
+            )),
+            None
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    ffi::OsString,
+    io,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+};
+
+/// Everything needed to start a killed process again the way it was started.
+#[derive(Debug, Clone)]
+pub struct ProcessSnapshot {
+    pub command_line: Vec<OsString>,
+    pub cwd: PathBuf,
+    pub env: Vec<(OsString, OsString)>,
+}
+
+/// Reads the argv, cwd and environment of a running process from /proc.
+#[cfg(target_os = "linux")]
+pub fn snapshot(pid: u32) -> io::Result<ProcessSnapshot> {
+    let proc_dir = PathBuf::from(format!("/proc/{pid}"));
+    let command_line = split_nul(&std::fs::read(proc_dir.join("cmdline"))?);
+    let cwd = std::fs::read_link(proc_dir.join("cwd"))?;
+    let env = split_nul(&std::fs::read(proc_dir.join("environ"))?);
+    ProcessSnapshot::new(command_line, cwd, env)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn snapshot(pid: u32) -> io::Result<ProcessSnapshot> {
+    use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[Pid::from_u32(pid)]),
+        true,
+        ProcessRefreshKind::nothing()
+            .with_cmd(UpdateKind::Always)
+            .with_cwd(UpdateKind::Always)
+            .with_environ(UpdateKind::Always),
+    );
+    let proc = system
+        .process(Pid::from_u32(pid))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "process is not running"))?;
+    let cwd = proc
+        .cwd()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "cwd is not readable"))?;
+    ProcessSnapshot::new(
+        proc.cmd().to_vec(),
+        cwd.to_path_buf(),
+        proc.environ().to_vec(),
+    )
+}
+
+impl ProcessSnapshot {
+    fn new(command_line: Vec<OsString>, cwd: PathBuf, env: Vec<OsString>) -> io::Result<Self> {
+        // Kernel threads and zombies have an empty cmdline.
+        if command_line.is_empty() {
+            return Err(io::Error::other("process has no command line to relaunch"));
+        }
+        Ok(Self {
+            command_line,
+            cwd,
+            env: env.iter().filter_map(split_env).collect(),
+        })
+    }
+
+    pub fn program_name(&self) -> String {
+        let program = PathBuf::from(&self.command_line[0]);
+        program
+            .file_name()
+            .unwrap_or(program.as_os_str())
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Starts the process again, detached from Port-o-Potty: its own process
+    /// group, no stdio, and only the captured environment.
+    pub fn spawn(&self) -> io::Result<Child> {
+        let (program, args) = self
+            .command_line
+            .split_first()
+            .ok_or_else(|| io::Error::other("process has no command line to relaunch"))?;
+        // `./server` style argv[0] is relative to the process's own cwd, not ours.
+        let program = PathBuf::from(program);
+        let program = if program.is_relative() && program.components().count() > 1 {
+            self.cwd.join(program)
+        } else {
+            program
+        };
+
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .current_dir(&self.cwd)
+            .env_clear()
+            .envs(self.env.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        #[cfg(not(windows))]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        command.spawn()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn split_nul(bytes: &[u8]) -> Vec<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    // Each entry is NUL-terminated; empty arguments in between are real.
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+    bytes
+        .split(|&b| b == 0)
+        .map(|part| std::ffi::OsStr::from_bytes(part).to_os_string())
+        .collect()
+}
+
+/// Splits `KEY=value`. Entries that aren't valid UTF-8 are dropped rather
+/// than relaunched mangled.
+fn split_env(entry: &OsString) -> Option<(OsString, OsString)> {
+    let text = entry.to_str()?;
+    // Search from the second byte so Windows' hidden "=C:=C:\" entries keep
+    // their key.
+    let split = text.get(1..)?.find('=')? + 1;
+    Some((text[..split].into(), text[split + 1..].into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn splits_proc_cmdline_and_environ() {
+        assert_eq!(
+            split_nul(b"node\0server.js\0--title\0\0--port\x003000\0"),
+            ["node", "server.js", "--title", "", "--port", "3000"]
+        );
+        let env: Vec<_> = split_nul(b"PATH=/usr/bin\0EMPTY=\0A=b=c\0NOVALUE\0")
+            .iter()
+            .filter_map(split_env)
+            .collect();
+        assert_eq!(
+            env,
+            [
+                ("PATH".into(), "/usr/bin".into()),
+                ("EMPTY".into(), "".into()),
+                ("A".into(), "b=c".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshots_own_process() {
+        let snapshot = snapshot(std::process::id()).unwrap();
+
+        assert_eq!(snapshot.cwd, std::env::current_dir().unwrap());
+        assert!(snapshot.env.iter().any(|(key, _)| key == "PATH"));
+        assert!(!snapshot.program_name().is_empty());
+    }
+}
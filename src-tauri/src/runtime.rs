@@ -0,0 +1,593 @@
+//! The container runtimes whose published ports we attribute and whose
+//! containers a disconnect stops: Docker and Podman through their Engine API
+//! sockets, and nerdctl (containerd) through its CLI, since containerd has no
+//! HTTP API.
+
+use crate::{
+    docker::{self, Docker, DockerError, Endpoint},
+    Protocol,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt,
+    io::{self, ErrorKind, Read},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+pub use docker::DEFAULT_TIMEOUT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeKind {
+    Docker,
+    Podman,
+    Nerdctl,
+}
+
+impl RuntimeKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            RuntimeKind::Docker => "Docker",
+            RuntimeKind::Podman => "Podman",
+            RuntimeKind::Nerdctl => "nerdctl",
+        }
+    }
+}
+
+/// A running container and the ports it publishes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    pub id: String,
+    pub name: String,
+    pub ports: Vec<PortBinding>,
+    pub compose: Option<ComposeInfo>,
+    /// Its addresses on the runtime's networks, which a userland proxy
+    /// forwards to.
+    pub addresses: Vec<IpAddr>,
+}
+
+#[cfg(test)]
+impl Container {
+    /// A container named after its ID that publishes nothing, for tests to
+    /// fill in with struct update syntax.
+    pub fn named(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            ports: Vec::new(),
+            compose: None,
+            addresses: Vec::new(),
+        }
+    }
+}
+
+/// Where a container came from when Compose started it. Docker Compose,
+/// podman-compose and nerdctl compose all set the same labels.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComposeInfo {
+    pub project: String,
+    pub service: String,
+    pub working_dir: Option<String>,
+}
+
+impl ComposeInfo {
+    pub fn from_labels(labels: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            project: labels.get("com.docker.compose.project")?.clone(),
+            service: labels.get("com.docker.compose.service")?.clone(),
+            working_dir: labels
+                .get("com.docker.compose.project.working_dir")
+                .cloned(),
+        })
+    }
+}
+
+/// One exposed container port. `host_port` is `None` when it isn't published.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortBinding {
+    pub host_ip: Option<IpAddr>,
+    pub host_port: Option<u16>,
+    pub container_port: u16,
+    pub protocol: Protocol,
+}
+
+#[cfg(test)]
+impl PortBinding {
+    /// A binding of `container_port` on `host_ip:host_port`, or an exposed
+    /// but unpublished port when both are `None`.
+    pub fn new(
+        host_ip: Option<&str>,
+        host_port: Option<u16>,
+        container_port: u16,
+        protocol: Protocol,
+    ) -> Self {
+        Self {
+            host_ip: host_ip.map(|ip| ip.parse().unwrap()),
+            host_port,
+            container_port,
+            protocol,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    /// The runtime isn't installed or isn't running.
+    Unavailable(String),
+    /// It is there but didn't answer in time, and the call was abandoned.
+    TimedOut(String),
+    Failed(String),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::Unavailable(message)
+            | RuntimeError::TimedOut(message)
+            | RuntimeError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<DockerError> for RuntimeError {
+    fn from(error: DockerError) -> Self {
+        match error {
+            DockerError::Unavailable(_) => RuntimeError::Unavailable(error.to_string()),
+            DockerError::TimedOut(_) => RuntimeError::TimedOut(error.to_string()),
+            _ => RuntimeError::Failed(error.to_string()),
+        }
+    }
+}
+
+/// A container runtime. Every call gives up once the runtime has had the
+/// timeout it was created with (plus, for `stop`, the grace period).
+pub trait ContainerRuntime: Send + Sync {
+    fn kind(&self) -> RuntimeKind;
+    /// Running containers.
+    fn containers(&self) -> Result<Vec<Container>, RuntimeError>;
+    /// Stops a container, giving it `timeout_secs` to exit before it is
+    /// killed.
+    fn stop(&self, id: &str, timeout_secs: u32) -> Result<(), RuntimeError>;
+    fn kill(&self, id: &str) -> Result<(), RuntimeError>;
+    fn start(&self, id: &str) -> Result<(), RuntimeError>;
+}
+
+/// A runtime to ask, or one that is set up but can't be.
+pub type ConfiguredRuntime = Result<Box<dyn ContainerRuntime>, (RuntimeKind, RuntimeError)>;
+
+/// Every runtime configured on this machine, Docker first, or why one that is
+/// configured can't be used (a Docker context that doesn't exist). Docker is
+/// left out when nothing points at it and its default socket isn't there, as
+/// it isn't installed. A Docker endpoint that is really Podman's socket is
+/// only listed as Podman.
+pub fn runtimes(timeout: Duration) -> Vec<ConfiguredRuntime> {
+    let podman = podman_endpoint();
+    let mut runtimes: Vec<ConfiguredRuntime> = Vec::new();
+    match Docker::from_env() {
+        Ok(docker) if docker.is_default() && !docker.endpoint().exists() => {}
+        Ok(docker) if Some(docker.endpoint()) == podman.as_ref() => {}
+        Ok(docker) => runtimes.push(Ok(Box::new(EngineApi {
+            kind: RuntimeKind::Docker,
+            client: docker.with_timeout(timeout),
+        }))),
+        Err(e) => runtimes.push(Err((RuntimeKind::Docker, e.into()))),
+    }
+    if let Some(endpoint) = podman {
+        runtimes.push(Ok(Box::new(EngineApi {
+            kind: RuntimeKind::Podman,
+            client: Docker::new(endpoint).with_timeout(timeout),
+        })));
+    }
+    if let Some(path) = nerdctl_path() {
+        runtimes.push(Ok(Box::new(Nerdctl { path, timeout })));
+    }
+    runtimes
+}
+
+pub fn runtime(
+    kind: RuntimeKind,
+    timeout: Duration,
+) -> Result<Box<dyn ContainerRuntime>, RuntimeError> {
+    let client = match kind {
+        RuntimeKind::Docker => Docker::from_env()?,
+        RuntimeKind::Podman => Docker::new(podman_endpoint().ok_or_else(|| {
+            RuntimeError::Unavailable("no Podman API socket was found".to_string())
+        })?),
+        RuntimeKind::Nerdctl => {
+            let path = nerdctl_path()
+                .ok_or_else(|| RuntimeError::Unavailable("nerdctl is not installed".to_string()))?;
+            return Ok(Box::new(Nerdctl { path, timeout }));
+        }
+    };
+    Ok(Box::new(EngineApi {
+        kind,
+        client: client.with_timeout(timeout),
+    }))
+}
+
+/// `CONTAINER_HOST`, or the first Podman API socket that exists: rootless,
+/// then rootful, then a `podman machine` VM's forwarded socket.
+fn podman_endpoint() -> Option<Endpoint> {
+    if let Some(host) = env::var("CONTAINER_HOST").ok().filter(|h| !h.is_empty()) {
+        return docker::parse_host(&host).ok();
+    }
+    [
+        env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("podman/podman.sock")),
+        Some(PathBuf::from("/run/podman/podman.sock")),
+        docker::home_dir()
+            .map(|home| home.join(".local/share/containers/podman/machine/podman.sock")),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|path| path.exists())
+    .map(Endpoint::Unix)
+}
+
+/// Docker, or Podman's Docker-compatible API service.
+struct EngineApi {
+    kind: RuntimeKind,
+    client: Docker,
+}
+
+impl ContainerRuntime for EngineApi {
+    fn kind(&self) -> RuntimeKind {
+        self.kind
+    }
+
+    fn containers(&self) -> Result<Vec<Container>, RuntimeError> {
+        Ok(self.client.containers()?)
+    }
+
+    fn stop(&self, id: &str, timeout_secs: u32) -> Result<(), RuntimeError> {
+        Ok(self.client.stop(id, timeout_secs)?)
+    }
+
+    fn kill(&self, id: &str) -> Result<(), RuntimeError> {
+        Ok(self.client.kill(id)?)
+    }
+
+    fn start(&self, id: &str) -> Result<(), RuntimeError> {
+        Ok(self.client.start(id)?)
+    }
+}
+
+/// containerd through the nerdctl CLI. Containers are read with
+/// `nerdctl inspect`, whose Docker-compatible output has structured ports.
+struct Nerdctl {
+    path: &'static Path,
+    timeout: Duration,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InspectedContainer {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    config: Option<InspectedConfig>,
+    #[serde(default)]
+    network_settings: Option<NetworkSettings>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InspectedConfig {
+    #[serde(default)]
+    labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NetworkSettings {
+    #[serde(default)]
+    ports: Option<BTreeMap<String, Option<Vec<HostBinding>>>>,
+    #[serde(default)]
+    networks: Option<BTreeMap<String, Network>>,
+}
+
+#[derive(Deserialize)]
+struct Network {
+    #[serde(rename = "IPAddress", default)]
+    ip_address: Option<String>,
+    #[serde(rename = "GlobalIPv6Address", default)]
+    global_ipv6_address: Option<String>,
+}
+
+/// A container's addresses across the networks it is attached to.
+pub fn network_addresses<'a, I>(networks: I) -> Vec<IpAddr>
+where
+    I: IntoIterator<Item = (&'a Option<String>, &'a Option<String>)>,
+{
+    let mut addresses: Vec<IpAddr> = networks
+        .into_iter()
+        .flat_map(|(v4, v6)| [v4, v6])
+        .filter_map(|address| address.as_deref()?.parse().ok())
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+    addresses
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HostBinding {
+    #[serde(default)]
+    host_ip: String,
+    #[serde(default)]
+    host_port: String,
+}
+
+impl ContainerRuntime for Nerdctl {
+    fn kind(&self) -> RuntimeKind {
+        RuntimeKind::Nerdctl
+    }
+
+    fn containers(&self) -> Result<Vec<Container>, RuntimeError> {
+        let ids = self
+            .run(&["ps", "--quiet", "--no-trunc"], self.timeout)
+            .map_err(|e| match e {
+                RuntimeError::Failed(message) => RuntimeError::Unavailable(message),
+                unavailable => unavailable,
+            })?;
+        let ids: Vec<&str> = ids.split_whitespace().collect();
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut args = vec!["container", "inspect"];
+        args.extend(&ids);
+        parse_inspect(&self.run(&args, self.timeout)?)
+    }
+
+    fn stop(&self, id: &str, timeout_secs: u32) -> Result<(), RuntimeError> {
+        self.run(
+            &["stop", "--time", &timeout_secs.to_string(), id],
+            self.timeout + Duration::from_secs(timeout_secs.into()),
+        )
+        .map(drop)
+    }
+
+    fn kill(&self, id: &str) -> Result<(), RuntimeError> {
+        self.run(&["kill", id], self.timeout).map(drop)
+    }
+
+    fn start(&self, id: &str) -> Result<(), RuntimeError> {
+        self.run(&["start", id], self.timeout).map(drop)
+    }
+}
+
+fn parse_inspect(json: &str) -> Result<Vec<Container>, RuntimeError> {
+    let inspected: Vec<InspectedContainer> = serde_json::from_str(json)
+        .map_err(|e| RuntimeError::Failed(format!("unexpected nerdctl inspect output: {e}")))?;
+    Ok(inspected
+        .into_iter()
+        .map(|container| {
+            let mut ports = Vec::new();
+            let (exposed, networks) = container
+                .network_settings
+                .map(|settings| (settings.ports, settings.networks))
+                .unwrap_or_default();
+            let addresses = network_addresses(
+                networks
+                    .iter()
+                    .flatten()
+                    .map(|(_, network)| (&network.ip_address, &network.global_ipv6_address)),
+            );
+            for (exposed, bindings) in exposed.unwrap_or_default() {
+                let Some((port, protocol)) = exposed.split_once('/') else {
+                    continue;
+                };
+                let (Ok(container_port), Some(protocol)) = (port.parse(), protocol_named(protocol))
+                else {
+                    continue;
+                };
+                let bindings = bindings.unwrap_or_default();
+                if bindings.is_empty() {
+                    ports.push(PortBinding {
+                        host_ip: None,
+                        host_port: None,
+                        container_port,
+                        protocol,
+                    });
+                }
+                for binding in bindings {
+                    ports.push(PortBinding {
+                        host_ip: binding.host_ip.parse().ok(),
+                        host_port: binding.host_port.parse().ok(),
+                        container_port,
+                        protocol,
+                    });
+                }
+            }
+            let name = match container.name.trim_start_matches('/') {
+                "" => container.id.chars().take(12).collect(),
+                name => name.to_string(),
+            };
+            let compose = container
+                .config
+                .and_then(|config| config.labels)
+                .and_then(|labels| ComposeInfo::from_labels(&labels));
+            Container {
+                id: container.id,
+                name,
+                ports,
+                compose,
+                addresses,
+            }
+        })
+        .collect())
+}
+
+/// A full container ID: 64 hex digits, as Docker, Podman and containerd all
+/// use.
+pub fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn protocol_named(name: &str) -> Option<Protocol> {
+    match name {
+        "tcp" => Some(Protocol::Tcp),
+        "udp" => Some(Protocol::Udp),
+        _ => None,
+    }
+}
+
+/// Where nerdctl is installed, looked up once. Rancher Desktop installs it
+/// outside the PATH a GUI app sees.
+fn nerdctl_path() -> Option<&'static Path> {
+    static PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+    PATH.get_or_init(|| {
+        let name = format!("nerdctl{}", env::consts::EXE_SUFFIX);
+        let mut dirs: Vec<PathBuf> = env::var_os("PATH")
+            .map(|path| env::split_paths(&path).collect())
+            .unwrap_or_default();
+        dirs.push(PathBuf::from("/usr/local/bin"));
+        dirs.extend(docker::home_dir().map(|home| home.join(".rd/bin")));
+        dirs.into_iter()
+            .map(|dir| dir.join(&name))
+            .find(|candidate| candidate.is_file())
+    })
+    .as_deref()
+}
+
+impl Nerdctl {
+    /// Runs nerdctl and returns its stdout, killing it after `timeout`.
+    fn run(&self, args: &[&str], timeout: Duration) -> Result<String, RuntimeError> {
+        let output =
+            output_within(Command::new(self.path).args(args), timeout).map_err(|e| {
+                match e.kind() {
+                    ErrorKind::TimedOut => {
+                        RuntimeError::TimedOut(format!("nerdctl is not responding: {e}"))
+                    }
+                    _ => RuntimeError::Unavailable(format!("nerdctl: {e}")),
+                }
+            })?;
+        if !output.status.success() {
+            return Err(RuntimeError::Failed(format!(
+                "nerdctl {} failed (exit {:?}): {}",
+                args.first().copied().unwrap_or_default(),
+                output.status.code(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Runs `command` to completion, killing it if it is still running after
+/// `timeout`.
+fn output_within(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drained as it runs, so a child with a lot to say can't fill a pipe and
+    // stall before exiting.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                format!("no answer within {} ms", timeout.as_millis()),
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        output
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_published_ports_from_nerdctl_inspect() {
+        let json = r#"[{
+            "Id": "9a3e",
+            "Name": "api",
+            "Config": {"Labels": {
+                "com.docker.compose.project": "shop",
+                "com.docker.compose.service": "api",
+                "nerdctl/platform": "linux/arm64"
+            }},
+            "NetworkSettings": {"Ports": {
+                "8080/tcp": [{"HostIp": "0.0.0.0", "HostPort": "18080"},
+                             {"HostIp": "::", "HostPort": "18080"}],
+                "9090/tcp": null,
+                "53/udp": [{"HostIp": "127.0.0.1", "HostPort": "5353"}]
+            }, "Networks": {"unknown-eth0": {
+                "IPAddress": "10.4.0.12", "GlobalIPv6Address": ""
+            }}}
+        }, {"Id": "0f00ba7c0ffee", "Name": "", "NetworkSettings": {"Ports": {}}}]"#;
+
+        assert_eq!(
+            parse_inspect(json).unwrap(),
+            [
+                Container {
+                    id: "9a3e".to_string(),
+                    name: "api".to_string(),
+                    ports: vec![
+                        PortBinding::new(Some("127.0.0.1"), Some(5353), 53, Protocol::Udp),
+                        PortBinding::new(Some("0.0.0.0"), Some(18080), 8080, Protocol::Tcp),
+                        PortBinding::new(Some("::"), Some(18080), 8080, Protocol::Tcp),
+                        PortBinding::new(None, None, 9090, Protocol::Tcp),
+                    ],
+                    compose: Some(ComposeInfo {
+                        project: "shop".to_string(),
+                        service: "api".to_string(),
+                        working_dir: None,
+                    }),
+                    addresses: vec!["10.4.0.12".parse().unwrap()],
+                },
+                Container {
+                    id: "0f00ba7c0ffee".to_string(),
+                    name: "0f00ba7c0ffe".to_string(),
+                    ports: vec![],
+                    compose: None,
+                    addresses: vec![],
+                },
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kills_a_command_that_outlives_its_timeout() {
+        let output = output_within(Command::new("echo").arg("hi"), Duration::from_secs(5)).unwrap();
+        assert_eq!(output.stdout, b"hi\n");
+
+        let started = Instant::now();
+        let error =
+            output_within(Command::new("sleep").arg("30"), Duration::from_millis(100)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
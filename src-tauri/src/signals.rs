@@ -0,0 +1,205 @@
+use crate::escalation::Signal;
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+/// How often to re-check liveness where we can't wait on a pidfd.
+const POLL_INTERVAL: Duration = Duration::from_millis(25);
+
+#[derive(Debug)]
+pub enum SignalError {
+    NoSuchProcess,
+    PermissionDenied,
+    Other(io::Error),
+}
+
+fn signal_number(signal: Signal) -> libc::c_int {
+    match signal {
+        Signal::Int => libc::SIGINT,
+        Signal::Term => libc::SIGTERM,
+        Signal::Hup => libc::SIGHUP,
+        Signal::Quit => libc::SIGQUIT,
+        Signal::Kill => libc::SIGKILL,
+    }
+}
+
+pub fn send(pid: u32, signal: Signal) -> Result<(), SignalError> {
+    kill(pid, signal_number(signal))
+}
+
+/// A process we may not signal still exists, so only ESRCH counts as gone.
+pub fn is_running(pid: u32) -> bool {
+    !matches!(kill(pid, 0), Err(SignalError::NoSuchProcess))
+}
+
+fn kill(pid: u32, signum: libc::c_int) -> Result<(), SignalError> {
+    // kill(2) treats 0 and negative pids as process groups (-1 is "everyone"),
+    // so never let one through.
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) if pid > 0 => pid,
+        _ => return Err(SignalError::NoSuchProcess),
+    };
+    // SAFETY: kill(2) takes no pointers; `pid` is positive, so it names one
+    // process and never a group.
+    if unsafe { libc::kill(pid, signum) } == 0 {
+        return Ok(());
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::ESRCH) => Err(SignalError::NoSuchProcess),
+        Some(libc::EPERM) => Err(SignalError::PermissionDenied),
+        _ => Err(SignalError::Other(error)),
+    }
+}
+
+pub fn process_group(pid: u32) -> Option<u32> {
+    let pid = libc::pid_t::try_from(pid).ok()?;
+    // SAFETY: getpgid(2) takes no pointers and fails cleanly for unknown pids.
+    let pgid = unsafe { libc::getpgid(pid) };
+    (pgid > 0).then_some(pgid as u32)
+}
+
+/// The session `pid` belongs to; a session leader's is its own pid.
+pub fn session_of(pid: u32) -> Option<u32> {
+    let pid = libc::pid_t::try_from(pid).ok()?;
+    // SAFETY: getsid(2) takes no pointers and fails cleanly for unknown pids.
+    let sid = unsafe { libc::getsid(pid) };
+    (sid > 0).then_some(sid as u32)
+}
+
+/// Blocks until none of `pids` is running or `timeout` elapses, returning
+/// whether they all exited.
+pub fn wait_for_exit(pids: &[u32], timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+
+    #[cfg(target_os = "linux")]
+    if let Some(exited) = pidfd::wait_for_exit(pids, deadline) {
+        return exited;
+    }
+
+    loop {
+        if !pids.iter().any(|&pid| is_running(pid)) {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod pidfd {
+    use std::{
+        io,
+        os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        time::Instant,
+    };
+
+    /// Waits on a pidfd per process, which becomes readable when the process
+    /// exits. Returns `None` when pidfds are unavailable (kernels before 5.3)
+    /// so the caller can fall back to polling.
+    pub fn wait_for_exit(pids: &[u32], deadline: Instant) -> Option<bool> {
+        let mut fds = Vec::with_capacity(pids.len());
+        for &pid in pids {
+            match open(pid) {
+                Ok(fd) => fds.push(fd),
+                Err(error) if error.raw_os_error() == Some(libc::ESRCH) => {}
+                Err(_) => return None,
+            }
+        }
+
+        let mut pending: Vec<libc::pollfd> = fds
+            .iter()
+            .map(|fd| libc::pollfd {
+                fd: fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        loop {
+            if pending.is_empty() {
+                return Some(true);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Some(false);
+            }
+            let timeout_ms = remaining.as_millis().clamp(1, libc::c_int::MAX as u128);
+            // SAFETY: `pending` is a live, exclusively borrowed slice of
+            // `len()` pollfds, whose fds stay open in `fds` until we return.
+            let ready = unsafe {
+                libc::poll(
+                    pending.as_mut_ptr(),
+                    pending.len() as libc::nfds_t,
+                    timeout_ms as libc::c_int,
+                )
+            };
+            if ready < 0 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return None;
+            }
+            pending.retain(|fd| fd.revents == 0);
+        }
+    }
+
+    fn open(pid: u32) -> io::Result<OwnedFd> {
+        // SAFETY: pidfd_open(2) takes a pid and flags, no pointers.
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the fd was just returned to us by pidfd_open and nothing
+        // else owns it.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn signals_and_waits_for_a_child_to_exit() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+
+        assert!(is_running(pid));
+        send(pid, Signal::Term).unwrap();
+        // Reap on another thread: an unreaped child stays a zombie and never
+        // looks exited to kill(2).
+        let reaper = thread::spawn(move || child.wait());
+
+        assert!(wait_for_exit(&[pid], Duration::from_secs(5)));
+        assert!(!reaper.join().unwrap().unwrap().success());
+    }
+
+    #[test]
+    fn reports_processes_that_do_not_exist() {
+        // Above every kernel's pid limit, so it can't have been reused.
+        let missing = libc::pid_t::MAX as u32;
+
+        assert!(!is_running(missing));
+        assert!(matches!(
+            send(missing, Signal::Term),
+            Err(SignalError::NoSuchProcess)
+        ));
+    }
+
+    #[test]
+    fn refuses_pids_that_kill_would_treat_as_groups() {
+        assert!(matches!(
+            send(0, Signal::Term),
+            Err(SignalError::NoSuchProcess)
+        ));
+        assert!(matches!(
+            send(u32::MAX, Signal::Term),
+            Err(SignalError::NoSuchProcess)
+        ));
+    }
+}
@@ -1,17 +1,187 @@
-import { useCallback, useEffect, useMemo, useRef, useState } from "react";
+import { Fragment, useCallback, useEffect, useMemo, useRef, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { loadRanges, saveRanges, type PortRange } from "./storage";
+import { listen } from "@tauri-apps/api/event";
+import {
+  loadContainerScope,
+  loadEscalationRules,
+  loadKillScope,
+  loadMonitorInterval,
+  loadProtected,
+  loadRanges,
+  loadRuntimeTimeout,
+  saveContainerScope,
+  saveEscalationRules,
+  saveKillScope,
+  saveMonitorInterval,
+  saveProtected,
+  saveRanges,
+  saveRuntimeTimeout,
+  type ContainerScope,
+  type EscalationRule,
+  type EscalationStep,
+  type KillScope,
+  type PortRange,
+  type ProtectedEntry,
+  type ProtectedKind,
+  type Signal
+} from "./storage";
 
-type Listener = {
-  port: number;
+type Protocol = "tcp" | "udp";
+type Exposure = "loopback" | "lan" | "all_interfaces";
+type ContainerRuntime = "docker" | "podman" | "nerdctl";
+
+// For a TCP listener `received` is the accept backlog and `sent` its limit;
+// for UDP both are bytes.
+type SocketQueue = { received: number; sent: number };
+
+type BoundSocket = {
+  local_addr: string;
+  address_family: "ipv4" | "ipv6";
+  exposure: Exposure;
+  // Only read over sock_diag.
+  queue: SocketQueue | null;
+  user: string | null;
+};
+
+type ProjectInfo = {
+  name: string;
+  root: string;
+  git_branch?: string | null;
+};
+
+type ListenerProcess = {
   pid: number;
   process_name?: string | null;
   started_seconds_ago?: number | null;
+  listening_seconds_ago?: number | null;
+  listening_before_launch: boolean;
+  command_line: string[];
+  cwd?: string | null;
+  exe_path?: string | null;
+  user?: string | null;
+  parent_pid?: number | null;
+  project?: ProjectInfo | null;
+  // Set when this is a userland proxy (docker-proxy, rootlesskit, ...) forwarding to a container.
+  proxy?: Proxy | null;
+  // Opaque token the backend uses to make sure the pid wasn't reused since this scan.
+  identity: string;
+};
+
+type Proxy = {
+  kind: "docker_proxy" | "rootlesskit" | "rootlessport" | "slirp4netns";
+  container_ip?: string | null;
+  container_port?: number | null;
+  container_id?: string | null;
+};
+
+type ConnectionPeer = {
+  remote_addr: string;
+  remote_port: number;
+  state: "established" | "time_wait" | "close_wait";
+  pid?: number | null;
+  process_name?: string | null;
+};
+
+type ConnectionSummary = {
+  established: number;
+  time_wait: number;
+  close_wait: number;
+  peers: ConnectionPeer[];
+};
+
+type Listener = {
+  port: number;
+  protocol: Protocol;
+  exposure: Exposure;
+  sockets: BoundSocket[];
+  processes: ListenerProcess[];
+  connections: ConnectionSummary;
   container_id?: string | null;
   container_name?: string | null;
+  container_runtime?: ContainerRuntime | null;
+  compose?: ComposeInfo | null;
+  container_bindings: ContainerBinding[];
+};
+
+type ContainerBinding = {
+  container_id: string;
+  container_name: string;
+  runtime: ContainerRuntime;
+  host_ip?: string | null;
+  host_port?: number | null;
+  container_port: number;
+  protocol: Protocol;
+};
+
+type ComposeInfo = {
+  project: string;
+  service: string;
+  working_dir?: string | null;
+};
+
+type UnavailableRuntime = {
+  runtime: ContainerRuntime;
+  message: string;
+};
+
+type ScanResult = {
+  listeners: Listener[];
+  scan_duration_ms: number;
+  // Runtimes that are set up but couldn't be reached or didn't answer; their containers are missing.
+  unavailable_runtimes: UnavailableRuntime[];
+  // Why sockets are read through the slower netstat2 fallback, if they are.
+  socket_fallback?: string | null;
+};
+
+type ScanCompleted = Omit<ScanResult, "listeners">;
+
+type KillPreview = {
+  root_pid: number;
+  root_name?: string | null;
+  count: number;
 };
 
-type SortKey = "port" | "process" | "pid" | "started";
+type ConnectionsChanged = Pick<Listener, "port" | "protocol" | "connections">;
+
+type KillError =
+  | { kind: "no_such_process"; pid: number; message: string }
+  | { kind: "permission_denied"; pid: number; message: string }
+  | { kind: "identity_mismatch"; pid: number; message: string }
+  | { kind: "protected"; pid: number; message: string }
+  | { kind: "container_mismatch"; message: string }
+  | { kind: "failed"; message: string };
+
+type KilledProcess = {
+  id: number;
+  pid: number;
+  process_name: string;
+  command_line: string[];
+  cwd: string;
+  listening: { port: number; protocol: Protocol }[];
+  killed_at: number;
+};
+
+type StoppedContainer = {
+  id: string;
+  name: string;
+  runtime: ContainerRuntime;
+  listening: { port: number; protocol: Protocol }[];
+  stopped_at: number;
+};
+
+type ListenerSession = {
+  port: number;
+  protocol: Protocol;
+  pid?: number | null;
+  process_name?: string | null;
+  command_line?: string | null;
+  container_name?: string | null;
+  first_seen: number;
+  disappeared_at?: number | null;
+  lifetime_seconds: number;
+};
+
+type SortKey = "port" | "address" | "process" | "pid" | "connections" | "started";
 type SortDir = "asc" | "desc";
 type ActionStatus = { kind: "info" | "success" | "error"; message: string };
 
@@ -26,6 +196,206 @@ function formatUptime(secondsAgo?: number | null) {
   return `${days}d ago`;
 }
 
+function formatDuration(seconds: number) {
+  if (seconds < 60) return `${seconds}s`;
+  const minutes = Math.floor(seconds / 60);
+  if (minutes < 60) return `${minutes}m`;
+  const hours = Math.floor(minutes / 60);
+  if (hours < 48) return `${hours}h ${minutes % 60}m`;
+  return `${Math.floor(hours / 24)}d ${hours % 24}h`;
+}
+
+function formatTimestamp(unixSeconds: number) {
+  return new Date(unixSeconds * 1000).toLocaleString();
+}
+
+const RUNTIME_LABELS: Record<ContainerRuntime, string> = {
+  docker: "Docker",
+  podman: "Podman",
+  nerdctl: "nerdctl"
+};
+
+function containerNoun(runtime?: ContainerRuntime | null) {
+  return runtime ? `${RUNTIME_LABELS[runtime]} container` : "container";
+}
+
+// "127.0.0.1:7777 → 8080/tcp", naming the container when several share the port.
+function formatBinding(binding: ContainerBinding, showContainer: boolean) {
+  const ip = binding.host_ip ?? "*";
+  const host = ip.includes(":") ? `[${ip}]` : ip;
+  const target = showContainer ? `${binding.container_name}:${binding.container_port}` : binding.container_port;
+  return `${host}:${binding.host_port} → ${target}/${binding.protocol}`;
+}
+
+const EXPOSURE_LABELS: Record<Exposure, string> = {
+  loopback: "Local only",
+  lan: "LAN",
+  all_interfaces: "All interfaces"
+};
+
+const EXPOSURE_RANK: Record<Exposure, number> = { loopback: 0, lan: 1, all_interfaces: 2 };
+
+function formatAddress(socket: BoundSocket) {
+  return socket.address_family === "ipv6" ? `[${socket.local_addr}]` : socket.local_addr;
+}
+
+function socketTitle(socket: BoundSocket, protocol: Protocol) {
+  const parts: string[] = [];
+  if (socket.user) parts.push(`owned by ${socket.user}`);
+  if (socket.queue) {
+    parts.push(
+      protocol === "tcp"
+        ? `${socket.queue.received} of ${socket.queue.sent} connections waiting to be accepted`
+        : `${socket.queue.received} bytes unread, ${socket.queue.sent} bytes unsent`
+    );
+  }
+  return parts.join("; ");
+}
+
+function processLabel(listener: Listener) {
+  const names = Array.from(new Set(listener.processes.map((p) => p.process_name).filter((n): n is string => !!n)));
+  return names.length ? names.join(", ") : null;
+}
+
+function processTitle(listener: Listener) {
+  return listener.processes
+    .map((p) => {
+      const lines = [`PID ${p.pid}${p.parent_pid != null ? ` (parent ${p.parent_pid})` : ""}${p.user ? ` · ${p.user}` : ""}`];
+      if (p.command_line.length) lines.push(p.command_line.join(" "));
+      if (p.exe_path) lines.push(`exe: ${p.exe_path}`);
+      if (p.cwd) lines.push(`cwd: ${p.cwd}`);
+      const forwardsTo = p.proxy ? proxyTarget(p.proxy) : null;
+      if (forwardsTo) lines.push(`forwards to ${forwardsTo}`);
+      return lines.join("\n");
+    })
+    .join("\n\n");
+}
+
+function proxyTarget(proxy: Proxy) {
+  if (proxy.container_ip) {
+    const host = proxy.container_ip.includes(":") ? `[${proxy.container_ip}]` : proxy.container_ip;
+    return proxy.container_port != null ? `${host}:${proxy.container_port}` : host;
+  }
+  return proxy.container_id ? `container ${proxy.container_id.slice(0, 12)}` : null;
+}
+
+function distinctProjects(listener: Listener) {
+  const byRoot = new Map<string, ProjectInfo>();
+  for (const p of listener.processes) {
+    if (p.project) byRoot.set(p.project.root, p.project);
+  }
+  return Array.from(byRoot.values());
+}
+
+function distinctCwds(listener: Listener) {
+  return Array.from(
+    new Set(listener.processes.filter((p) => !p.project).map((p) => p.cwd).filter((c): c is string => !!c))
+  );
+}
+
+function firstPid(listener: Listener) {
+  return listener.processes[0]?.pid ?? 0;
+}
+
+function oldestStart(listener: Listener) {
+  const started = listener.processes
+    .map((p) => p.started_seconds_ago)
+    .filter((s): s is number => s != null);
+  return started.length ? Math.max(...started) : null;
+}
+
+function longestListening(listener: Listener) {
+  const listening = listener.processes
+    .map((p) => p.listening_seconds_ago)
+    .filter((s): s is number => s != null);
+  return listening.length ? Math.max(...listening) : null;
+}
+
+// Listeners already up when Port-o-Potty launched have only a lower bound.
+function formatListening(listener: Listener) {
+  const text = formatUptime(longestListening(listener));
+  return listener.processes.some((p) => p.listening_before_launch) ? `≥ ${text}` : text;
+}
+
+function describePeers(connections: ConnectionSummary) {
+  const labels = connections.peers
+    .filter((p) => p.state === "established")
+    .map((p) => p.process_name ?? p.remote_addr);
+  return Array.from(new Set(labels)).join(", ");
+}
+
+function peerTitle(connections: ConnectionSummary) {
+  return connections.peers
+    .map((p) => `${p.remote_addr}:${p.remote_port} ${p.state}${p.process_name ? ` (${p.process_name} ${p.pid})` : ""}`)
+    .join("\n");
+}
+
+const SIGNALS: Signal[] = ["INT", "TERM", "HUP", "QUIT", "KILL"];
+
+// "INT:5000, KILL:800" -> [{ signal: "INT", timeout_ms: 5000 }, { signal: "KILL", timeout_ms: 800 }]
+function parseSteps(text: string): EscalationStep[] | null {
+  const steps: EscalationStep[] = [];
+  for (const part of text.split(",").map((p) => p.trim()).filter(Boolean)) {
+    const [name, ms] = part.split(":").map((p) => p.trim());
+    const signal = name.toUpperCase().replace(/^SIG/, "") as Signal;
+    const timeout = Number(ms ?? 1000);
+    if (!SIGNALS.includes(signal) || !Number.isFinite(timeout) || timeout < 0) return null;
+    steps.push({ signal, timeout_ms: Math.floor(timeout) });
+  }
+  return steps.length ? steps : null;
+}
+
+function formatSteps(steps: EscalationStep[]) {
+  return steps.map((s) => `${s.signal}:${s.timeout_ms}`).join(", ");
+}
+
+const PROTECTED_KIND_LABELS: Record<ProtectedKind, string> = {
+  name: "Process name",
+  exe_path: "Executable path",
+  user: "User",
+  pid: "PID"
+};
+
+// Every container the scan attributed the listener to, which the backend
+// checks are still the ones holding the port.
+function scannedContainers(listener: Listener) {
+  const ids = listener.container_bindings.map((b) => b.container_id);
+  if (listener.container_id) ids.push(listener.container_id);
+  return [...new Set(ids)];
+}
+
+// Every container a disconnect stops: all of those publishing the port have to
+// go for it to close, whichever bound socket was picked.
+function containerLabels(listener: Listener) {
+  const labels = new Map<string, string>();
+  if (listener.container_id && listener.container_name) {
+    labels.set(listener.container_id, `${containerNoun(listener.container_runtime)} ${listener.container_name}`);
+  }
+  for (const b of listener.container_bindings) {
+    if (!labels.has(b.container_id)) labels.set(b.container_id, `${containerNoun(b.runtime)} ${b.container_name}`);
+  }
+  return [...labels.values()];
+}
+
+function describeKillError(e: unknown) {
+  if (typeof e !== "object" || e == null || !("kind" in e)) return String(e);
+  const err = e as KillError;
+  switch (err.kind) {
+    case "no_such_process":
+      return `${err.message}. It may have already exited; refresh to see the current listeners.`;
+    case "identity_mismatch":
+      return `${err.message}. It exited and the pid may have been reused, so nothing was killed. Refresh and try again.`;
+    case "container_mismatch":
+      return `${err.message}, so nothing was stopped. Refresh and try again.`;
+    case "protected":
+      return `${err.message}. Protected processes are never signalled; remove the entry under Protected Processes if this is intended.`;
+    case "permission_denied":
+      return `${err.message}: it is owned by another user (often root). Stop it with sudo or its service manager.`;
+    default:
+      return err.message;
+  }
+}
+
 function compareNullable<T>(a: T | null | undefined, b: T | null | undefined, compare: (x: T, y: T) => number) {
   if (a == null && b == null) return 0;
   if (a == null) return 1;
@@ -48,13 +418,51 @@ export default function App() {
   const [end, setEnd] = useState<number>(3999);
   const [listeners, setListeners] = useState<Listener[]>([]);
   const [busy, setBusy] = useState(false);
+  const [scanDurationMs, setScanDurationMs] = useState<number | null>(null);
+  const [unavailableRuntimes, setUnavailableRuntimes] = useState<UnavailableRuntime[]>([]);
+  const [socketFallback, setSocketFallback] = useState<string | null>(null);
   const [error, setError] = useState<string | null>(null);
   const [actionStatus, setActionStatus] = useState<ActionStatus | null>(null);
   const [confirmKey, setConfirmKey] = useState<string | null>(null);
   const [disconnectingKey, setDisconnectingKey] = useState<string | null>(null);
-  const [inFocus, setInFocus] = useState(true);
+  const [monitorInterval, setMonitorInterval] = useState<number>(() => loadMonitorInterval());
+  const [runtimeTimeout, setRuntimeTimeout] = useState<number>(() => loadRuntimeTimeout());
   const [sortKey, setSortKey] = useState<SortKey>("port");
   const [sortDir, setSortDir] = useState<SortDir>("asc");
+  const [killScope, setKillScope] = useState<KillScope>(() => loadKillScope());
+  const [containerScope, setContainerScope] = useState<ContainerScope>(() => loadContainerScope());
+  const [escalationRules, setEscalationRules] = useState<EscalationRule[]>(() => loadEscalationRules());
+  const [rulePattern, setRulePattern] = useState("");
+  const [ruleSteps, setRuleSteps] = useState("INT:5000, TERM:5000, KILL:800");
+  const [protectedEntries, setProtectedEntries] = useState<ProtectedEntry[]>(() => loadProtected());
+  const [protectedKind, setProtectedKind] = useState<ProtectedKind>("name");
+  const [protectedValue, setProtectedValue] = useState("");
+  const [recentlyKilled, setRecentlyKilled] = useState<KilledProcess[]>([]);
+  const [recentlyStopped, setRecentlyStopped] = useState<StoppedContainer[]>([]);
+  const [historyPort, setHistoryPort] = useState("");
+  const [historyAt, setHistoryAt] = useState("");
+  const [historySessions, setHistorySessions] = useState<ListenerSession[] | null>(null);
+  const [retentionDays, setRetentionDays] = useState<number | null>(null);
+  const [restartingId, setRestartingId] = useState<number | string | null>(null);
+
+  useEffect(() => {
+    saveKillScope(killScope);
+  }, [killScope]);
+
+  useEffect(() => {
+    saveContainerScope(containerScope);
+  }, [containerScope]);
+
+  useEffect(() => {
+    saveEscalationRules(escalationRules);
+  }, [escalationRules]);
+
+  useEffect(() => {
+    saveProtected(protectedEntries);
+    invoke("set_protected_processes", { entries: protectedEntries }).catch((e) =>
+      setActionStatus({ kind: "error", message: `Protected list failed: ${String(e)}` })
+    );
+  }, [protectedEntries]);
 
   const rangesRef = useRef(ranges);
   const didMount = useRef(false);
@@ -78,11 +486,11 @@ export default function App() {
     setBusy(true);
     setError(null);
     try {
-      const data = await invoke<Listener[]>("scan_ports", { ranges: rangesRef.current });
-      const dedupKey = (l: Listener) => `${l.port}:${l.pid}`;
-      const uniq = new Map<string, Listener>();
-      for (const l of data) uniq.set(dedupKey(l), l);
-      setListeners(Array.from(uniq.values()));
+      const data = await invoke<ScanResult>("scan_ports", { ranges: rangesRef.current });
+      setListeners(data.listeners);
+      setScanDurationMs(data.scan_duration_ms);
+      setUnavailableRuntimes(data.unavailable_runtimes);
+      setSocketFallback(data.socket_fallback ?? null);
     } catch (e) {
       setError(String(e));
       setActionStatus({ kind: "error", message: `Refresh failed: ${String(e)}` });
@@ -93,44 +501,176 @@ export default function App() {
 
   useEffect(() => {
     refresh();
+    loadUndoHistory();
   }, [refresh]);
 
+  async function loadUndoHistory() {
+    try {
+      const [killed, stopped] = await Promise.all([
+        invoke<KilledProcess[]>("recently_killed"),
+        invoke<StoppedContainer[]>("recently_stopped")
+      ]);
+      setRecentlyKilled(killed);
+      setRecentlyStopped(stopped);
+    } catch {
+      // undo history is best-effort
+    }
+  }
+
+  async function restartKilled(killed: KilledProcess) {
+    setRestartingId(killed.id);
+    setActionStatus({ kind: "info", message: `Restarting ${killed.process_name}...` });
+    try {
+      const result = await invoke<string>("restart_killed", { id: killed.id });
+      setActionStatus({ kind: "success", message: result });
+    } catch (e) {
+      setActionStatus({ kind: "error", message: String(e) });
+    } finally {
+      setRestartingId(null);
+      loadUndoHistory();
+      refresh();
+    }
+  }
+
   useEffect(() => {
-    function updateFocus() {
-      const visible = document.visibilityState === "visible";
-      setInFocus(visible);
+    invoke<string | null>("history_unpersisted_reason").then((reason) => {
+      if (reason) {
+        setActionStatus({ kind: "error", message: `Listener history will not be saved: ${reason}` });
+      }
+    });
+    invoke<number>("history_retention")
+      .then(setRetentionDays)
+      .catch(() => setRetentionDays(null));
+  }, []);
+
+  async function changeRetention(days: number) {
+    try {
+      await invoke("set_history_retention", { days });
+      setRetentionDays(days);
+    } catch (e) {
+      setActionStatus({ kind: "error", message: `Could not change history retention: ${String(e)}` });
+    }
+  }
+
+  // With a time, who held the port then; otherwise everything in the last 24h.
+  async function lookUpHistory() {
+    const port = historyPort.trim() ? Number(historyPort) : null;
+    if (port != null && (!Number.isInteger(port) || port < 1 || port > 65535)) {
+      setActionStatus({ kind: "error", message: "History lookups need a port between 1 and 65535" });
+      return;
+    }
+    try {
+      let sessions: ListenerSession[];
+      if (historyAt) {
+        const at = Math.floor(new Date(historyAt).getTime() / 1000);
+        sessions = await invoke<ListenerSession[]>("listener_history", { port, from: at, to: at });
+      } else if (port != null) {
+        const now = Math.floor(Date.now() / 1000);
+        sessions = await invoke<ListenerSession[]>("listener_history", { port, from: now - 86_400, to: now });
+      } else {
+        sessions = await invoke<ListenerSession[]>("listener_lifetimes", { hours: 24 });
+      }
+      setHistorySessions(sessions);
+    } catch (e) {
+      setActionStatus({ kind: "error", message: `History lookup failed: ${String(e)}` });
     }
-    updateFocus();
-    document.addEventListener("visibilitychange", updateFocus);
-    window.addEventListener("focus", updateFocus);
-    window.addEventListener("blur", updateFocus);
+  }
+
+  async function restartContainer(container: StoppedContainer) {
+    setRestartingId(container.id);
+    setActionStatus({ kind: "info", message: `Starting ${containerNoun(container.runtime)} ${container.name}...` });
+    try {
+      const result = await invoke<string>("restart_container", { id: container.id });
+      setActionStatus({ kind: "success", message: result });
+    } catch (e) {
+      setActionStatus({ kind: "error", message: String(e) });
+    } finally {
+      setRestartingId(null);
+      loadUndoHistory();
+      refresh();
+    }
+  }
+
+  // The backend monitor rescans on its own, even while the window is hidden,
+  // and pushes the differences.
+  useEffect(() => {
+    saveMonitorInterval(monitorInterval);
+    invoke("configure_monitor", { config: { ranges, interval_ms: monitorInterval } }).catch((e) =>
+      setActionStatus({ kind: "error", message: `Monitor failed: ${String(e)}` })
+    );
+  }, [ranges, monitorInterval]);
+
+  useEffect(() => {
+    saveRuntimeTimeout(runtimeTimeout);
+    invoke("set_runtime_timeout", { timeoutMs: runtimeTimeout }).catch((e) =>
+      setActionStatus({ kind: "error", message: `Runtime timeout failed: ${String(e)}` })
+    );
+  }, [runtimeTimeout]);
+
+  useEffect(() => {
+    const upsert = (listener: Listener) =>
+      setListeners((prev) => [...prev.filter((l) => listenerKey(l) !== listenerKey(listener)), listener]);
+    const unlisteners = [
+      listen<Listener>("listener-added", (e) => upsert(e.payload)),
+      listen<Listener>("listener-changed", (e) => upsert(e.payload)),
+      listen<Listener>("listener-removed", (e) =>
+        setListeners((prev) => prev.filter((l) => listenerKey(l) !== listenerKey(e.payload)))
+      ),
+      listen<ConnectionsChanged>("listener-connections", (e) =>
+        setListeners((prev) =>
+          prev.map((l) =>
+            listenerKey(l) === listenerKey(e.payload) ? { ...l, connections: e.payload.connections } : l
+          )
+        )
+      ),
+      listen<string>("monitor-error", (e) => setActionStatus({ kind: "error", message: e.payload })),
+      listen<ScanCompleted>("scan-completed", (e) => {
+        setScanDurationMs(e.payload.scan_duration_ms);
+        setUnavailableRuntimes(e.payload.unavailable_runtimes);
+        setSocketFallback(e.payload.socket_fallback ?? null);
+      })
+    ];
     return () => {
-      document.removeEventListener("visibilitychange", updateFocus);
-      window.removeEventListener("focus", updateFocus);
-      window.removeEventListener("blur", updateFocus);
+      unlisteners.forEach((p) => p.then((unlisten) => unlisten()));
     };
   }, []);
 
-  useEffect(() => {
-    if (!inFocus) return;
-    const id = window.setInterval(() => {
-      refresh();
-    }, 15_000);
-    return () => window.clearInterval(id);
-  }, [inFocus, refresh]);
-
-  function listenerKey(listener: Listener) {
-    return `${listener.port}:${listener.pid}`;
+  function listenerKey(listener: Pick<Listener, "port" | "protocol">) {
+    return `${listener.protocol}:${listener.port}`;
   }
 
   async function disconnect(listener: Listener) {
     const key = listenerKey(listener);
+    const containers = containerLabels(listener);
     const target = listener.container_name
-      ? `container ${listener.container_name} on port ${listener.port}`
-      : `PID ${listener.pid}`;
+      ? containers.length > 1
+        ? `${containers.join(", ")}${containerScope === "container" ? "" : ` with their compose ${containerScope}s`} on ${listener.port}/${listener.protocol}`
+        : listener.compose && containerScope === "service"
+          ? `compose service ${listener.compose.service} of ${listener.compose.project}`
+          : listener.compose && containerScope === "project"
+            ? `compose project ${listener.compose.project}`
+            : `${containers[0]} on ${listener.port}/${listener.protocol}`
+      : listener.processes.length === 1
+        ? `PID ${firstPid(listener)}`
+        : `${listener.processes.length} processes on ${listener.port}/${listener.protocol}`;
     if (confirmKey !== key) {
       setConfirmKey(key);
-      setActionStatus({ kind: "info", message: `Ready to disconnect ${target}. Click Confirm to continue.` });
+      let extent = "";
+      if (!listener.container_name && killScope !== "process") {
+        // A tree or group can reach well above the listener; name its top.
+        try {
+          const preview = await invoke<KillPreview>("preview_kill", {
+            pids: listener.processes.map((p) => p.pid),
+            scope: killScope
+          });
+          extent = ` This kills ${preview.count} process${preview.count === 1 ? "" : "es"} under PID ${preview.root_pid}${
+            preview.root_name ? ` (${preview.root_name})` : ""
+          }.`;
+        } catch (e) {
+          extent = ` ${describeKillError(e)}`;
+        }
+      }
+      setActionStatus({ kind: "info", message: `Ready to disconnect ${target}.${extent} Click Confirm to continue.` });
       return;
     }
 
@@ -139,15 +679,28 @@ export default function App() {
     setError(null);
     setActionStatus({ kind: "info", message: `Disconnecting ${target}...` });
     try {
-      const result = await invoke<string>("disconnect_listener", { port: listener.port, pid: listener.pid });
-      setListeners((prev) => prev.filter((l) => l.port !== listener.port || l.pid !== listener.pid));
+      const result = await invoke<string>("disconnect_listener", {
+        port: listener.port,
+        protocol: listener.protocol,
+        targets: listener.processes.map((p) => p.identity),
+        containers: scannedContainers(listener),
+        options: {
+          scope: killScope,
+          container_scope: containerScope,
+          rules: escalationRules
+        }
+      });
+      setListeners((prev) => prev.filter((l) => listenerKey(l) !== key));
       setActionStatus({ kind: "success", message: result });
+      loadUndoHistory();
       // also refresh soon to catch port rebinds
       setTimeout(() => refresh(), 600);
     } catch (e) {
-      const message = String(e);
+      const message = describeKillError(e);
       setError(message);
       setActionStatus({ kind: "error", message });
+      const kind = (e as Partial<KillError> | null)?.kind;
+      if (kind === "identity_mismatch" || kind === "container_mismatch" || kind === "no_such_process") refresh();
     } finally {
       setDisconnectingKey(null);
     }
@@ -162,6 +715,45 @@ export default function App() {
     setRanges((prev) => prev.filter((_, i) => i !== index));
   }
 
+  function addEscalationRule() {
+    const steps = parseSteps(ruleSteps);
+    if (!rulePattern.trim() || !steps) {
+      setActionStatus({ kind: "error", message: "Rules need a process pattern and steps like INT:5000, KILL:800" });
+      return;
+    }
+    setEscalationRules((prev) => [
+      ...prev,
+      { pattern: rulePattern.trim(), policy: { steps, port_close_timeout_ms: 1500 } }
+    ]);
+    setRulePattern("");
+  }
+
+  function removeEscalationRule(index: number) {
+    setEscalationRules((prev) => prev.filter((_, i) => i !== index));
+  }
+
+  function addProtected() {
+    const value = protectedValue.trim();
+    let entry: ProtectedEntry;
+    if (protectedKind === "pid") {
+      const pid = Number(value);
+      if (!Number.isInteger(pid) || pid <= 0) {
+        setActionStatus({ kind: "error", message: "Protected PIDs must be positive whole numbers" });
+        return;
+      }
+      entry = { kind: "pid", value: pid };
+    } else {
+      if (!value) return;
+      entry = { kind: protectedKind, value };
+    }
+    setProtectedEntries((prev) => [...prev, entry]);
+    setProtectedValue("");
+  }
+
+  function removeProtected(index: number) {
+    setProtectedEntries((prev) => prev.filter((_, i) => i !== index));
+  }
+
   const sortedListeners = useMemo(() => {
     const dir = sortDir === "asc" ? 1 : -1;
     const byNumber = (a: number, b: number) => (a - b) * dir;
@@ -178,14 +770,21 @@ export default function App() {
         case "port":
           cmp = byNumber(a.port, b.port);
           break;
+        case "address":
+          cmp = byNumber(EXPOSURE_RANK[a.exposure], EXPOSURE_RANK[b.exposure]);
+          if (cmp === 0) cmp = byString(a.sockets[0]?.local_addr ?? "", b.sockets[0]?.local_addr ?? "");
+          break;
         case "pid":
-          cmp = byNumber(a.pid, b.pid);
+          cmp = byNumber(firstPid(a), firstPid(b));
           break;
         case "process":
-          cmp = compareNullable(a.process_name ?? null, b.process_name ?? null, byString);
+          cmp = compareNullable(processLabel(a), processLabel(b), byString);
+          break;
+        case "connections":
+          cmp = byNumber(a.connections.established, b.connections.established);
           break;
         case "started":
-          cmp = compareNullable(a.started_seconds_ago ?? null, b.started_seconds_ago ?? null, byUptime);
+          cmp = compareNullable(longestListening(a), longestListening(b), byUptime);
           break;
       }
 
@@ -193,13 +792,25 @@ export default function App() {
       // deterministic tie-breakers
       cmp = a.port - b.port;
       if (cmp !== 0) return cmp;
-      cmp = a.pid - b.pid;
+      cmp = a.protocol.localeCompare(b.protocol);
       if (cmp !== 0) return cmp;
       return aa.idx - bb.idx;
     });
     return base.map((x) => x.l);
   }, [listeners, sortDir, sortKey]);
 
+  // Listeners outside any compose project come first, then one group per project.
+  const listenerGroups = useMemo(() => {
+    const groups = new Map<string | null, Listener[]>();
+    for (const l of sortedListeners) {
+      const project = l.compose?.project ?? null;
+      groups.set(project, [...(groups.get(project) ?? []), l]);
+    }
+    return Array.from(groups, ([project, members]) => ({ project, listeners: members })).sort((a, b) =>
+      a.project === null ? -1 : b.project === null ? 1 : a.project.localeCompare(b.project)
+    );
+  }, [sortedListeners]);
+
   function toggleSort(nextKey: SortKey) {
     if (nextKey === sortKey) {
       setSortDir((d) => (d === "asc" ? "desc" : "asc"));
@@ -222,13 +833,35 @@ export default function App() {
           <div className="subtitle">Shows listeners in your configured port ranges.</div>
         </div>
         <div className="pill">
-          <span>{inFocus ? "Active" : "Paused"}</span>
+          <span>Live</span>
           <span className="muted">•</span>
-          <span className="muted">Refresh: 15s</span>
+          <select value={monitorInterval} onChange={(e) => setMonitorInterval(Number(e.target.value))}>
+            {[2000, 5000, 15000, 60000].map((ms) => (
+              <option key={ms} value={ms}>
+                every {ms / 1000}s
+              </option>
+            ))}
+          </select>
+          {scanDurationMs !== null ? (
+            <>
+              <span className="muted">•</span>
+              <span title="Time taken by the last scan">
+                {scanDurationMs < 10 ? scanDurationMs.toFixed(1) : Math.round(scanDurationMs)} ms
+              </span>
+            </>
+          ) : null}
         </div>
       </div>
 
       {error ? <div className="error">{error}</div> : null}
+      {unavailableRuntimes.map((r) => (
+        <div key={r.runtime} className="status info">
+          {r.message}. {RUNTIME_LABELS[r.runtime]} containers aren't shown until it answers.
+        </div>
+      ))}
+      {socketFallback ? (
+        <div className="status info">sock_diag failed ({socketFallback}); scanning with the slower netstat2 fallback.</div>
+      ) : null}
       {actionStatus ? <div className={`status ${actionStatus.kind}`}>{actionStatus.message}</div> : null}
 
       <div className="grid">
@@ -290,10 +923,164 @@ export default function App() {
               </tbody>
             </table>
           )}
+
+          <h2 className="section">Shutdown Rules</h2>
+          <div className="row">
+            <input
+              type="text"
+              placeholder="Process name or command"
+              value={rulePattern}
+              onChange={(e) => setRulePattern(e.target.value)}
+            />
+          </div>
+          <div className="row">
+            <input type="text" value={ruleSteps} onChange={(e) => setRuleSteps(e.target.value)} />
+            <button className="btn primary" onClick={addEscalationRule}>
+              Add
+            </button>
+          </div>
+          {escalationRules.length === 0 ? (
+            <div className="muted small">Default: TERM, then KILL after 800 ms.</div>
+          ) : (
+            <table className="table">
+              <tbody>
+                {escalationRules.map((rule, i) => (
+                  <tr key={`${rule.pattern}-${i}`}>
+                    <td>
+                      {rule.pattern}
+                      <div className="muted small">{formatSteps(rule.policy.steps)}</div>
+                    </td>
+                    <td style={{ width: 90 }}>
+                      <button className="btn danger" onClick={() => removeEscalationRule(i)}>
+                        Remove
+                      </button>
+                    </td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          )}
+
+          <h2 className="section">Protected Processes</h2>
+          <div className="row">
+            <select value={protectedKind} onChange={(e) => setProtectedKind(e.target.value as ProtectedKind)}>
+              {(Object.keys(PROTECTED_KIND_LABELS) as ProtectedKind[]).map((kind) => (
+                <option key={kind} value={kind}>
+                  {PROTECTED_KIND_LABELS[kind]}
+                </option>
+              ))}
+            </select>
+            <input type="text" value={protectedValue} onChange={(e) => setProtectedValue(e.target.value)} />
+            <button className="btn primary" onClick={addProtected}>
+              Add
+            </button>
+          </div>
+          <div className="muted small">
+            Always protected: PID 1, Port-o-Potty, sshd, systemd, launchd and the Docker daemons.
+          </div>
+          {protectedEntries.length > 0 && (
+            <table className="table">
+              <tbody>
+                {protectedEntries.map((entry, i) => (
+                  <tr key={`${entry.kind}-${entry.value}-${i}`}>
+                    <td>
+                      {entry.value}
+                      <div className="muted small">{PROTECTED_KIND_LABELS[entry.kind]}</div>
+                    </td>
+                    <td style={{ width: 90 }}>
+                      <button className="btn danger" onClick={() => removeProtected(i)}>
+                        Remove
+                      </button>
+                    </td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          )}
+
+          <h2 className="section">History</h2>
+          <div className="row">
+            <input
+              type="text"
+              placeholder="Port (blank for all)"
+              value={historyPort}
+              onChange={(e) => setHistoryPort(e.target.value)}
+            />
+            <button className="btn primary" onClick={lookUpHistory}>
+              Look up
+            </button>
+          </div>
+          <div className="row">
+            <input type="datetime-local" value={historyAt} onChange={(e) => setHistoryAt(e.target.value)} />
+          </div>
+          <div className="muted small">
+            {historyAt ? "Who held the port at that time." : "Everything seen in the last 24 hours."}{" "}
+            {retentionDays != null && (
+              <label>
+                Keep{" "}
+                <select value={retentionDays} onChange={(e) => changeRetention(Number(e.target.value))}>
+                  {[1, 7, 30, 90, 0].map((days) => (
+                    <option key={days} value={days}>
+                      {days === 0 ? "forever" : `${days} days`}
+                    </option>
+                  ))}
+                </select>
+              </label>
+            )}
+          </div>
+          {historySessions &&
+            (historySessions.length === 0 ? (
+              <div className="muted small">Nothing was listening.</div>
+            ) : (
+              <table className="table">
+                <tbody>
+                  {historySessions.map((s, i) => (
+                    <tr key={`${s.port}-${s.pid ?? "none"}-${s.first_seen}-${i}`}>
+                      <td>
+                        {`${s.port}/${s.protocol}`}
+                        <div className="muted small">{formatDuration(s.lifetime_seconds)}</div>
+                      </td>
+                      <td title={s.command_line ?? undefined}>
+                        {s.container_name ?? s.process_name ?? "—"}
+                        {s.pid != null && <span className="muted"> ({s.pid})</span>}
+                        <div className="muted small">
+                          {formatTimestamp(s.first_seen)} –{" "}
+                          {s.disappeared_at != null ? formatTimestamp(s.disappeared_at) : "now"}
+                        </div>
+                      </td>
+                    </tr>
+                  ))}
+                </tbody>
+              </table>
+            ))}
         </div>
 
         <div className="panel">
-          <h2>Listeners</h2>
+          <div className="row spread">
+            <h2>Listeners</h2>
+            <label className="muted small">
+              Kill{" "}
+              <select value={killScope} onChange={(e) => setKillScope(e.target.value as KillScope)}>
+                <option value="process">process only</option>
+                <option value="tree">process tree</option>
+                <option value="group">process group</option>
+              </select>
+              {" "}Stop{" "}
+              <select value={containerScope} onChange={(e) => setContainerScope(e.target.value as ContainerScope)}>
+                <option value="container">container only</option>
+                <option value="service">compose service</option>
+                <option value="project">compose project</option>
+              </select>
+              {" "}Runtime timeout{" "}
+              <select value={runtimeTimeout} onChange={(e) => setRuntimeTimeout(Number(e.target.value))}>
+                {[1000, 2000, 5000, 10000, 30000].map((ms) => (
+                  <option key={ms} value={ms}>
+                    {ms / 1000}s
+                  </option>
+                ))}
+              </select>
+            </label>
+          </div>
           {sortedListeners.length === 0 ? (
             <div className="empty">
               No listeners found in these ranges. {busy ? "Scanning…" : ""}
@@ -312,6 +1099,16 @@ export default function App() {
                       Port <span className="thicon">{sortIndicator("port")}</span>
                     </button>
                   </th>
+                  <th style={{ width: 150 }}>
+                    <button
+                      className="thbtn"
+                      onClick={() => toggleSort("address")}
+                      type="button"
+                      aria-sort={sortKey === "address" ? (sortDir === "asc" ? "ascending" : "descending") : "none"}
+                    >
+                      Bound to <span className="thicon">{sortIndicator("address")}</span>
+                    </button>
+                  </th>
                   <th>
                     <button
                       className="thbtn"
@@ -332,6 +1129,16 @@ export default function App() {
                       PID <span className="thicon">{sortIndicator("pid")}</span>
                     </button>
                   </th>
+                  <th style={{ width: 130 }}>
+                    <button
+                      className="thbtn"
+                      onClick={() => toggleSort("connections")}
+                      type="button"
+                      aria-sort={sortKey === "connections" ? (sortDir === "asc" ? "ascending" : "descending") : "none"}
+                    >
+                      Conns <span className="thicon">{sortIndicator("connections")}</span>
+                    </button>
+                  </th>
                   <th style={{ width: 120 }}>
                     <button
                       className="thbtn"
@@ -339,48 +1146,195 @@ export default function App() {
                       type="button"
                       aria-sort={sortKey === "started" ? (sortDir === "asc" ? "ascending" : "descending") : "none"}
                     >
-                      Started <span className="thicon">{sortIndicator("started")}</span>
+                      Listening <span className="thicon">{sortIndicator("started")}</span>
                     </button>
                   </th>
                   <th style={{ width: 110 }} />
                 </tr>
               </thead>
               <tbody>
-                {sortedListeners.map((l) => (
-                  <tr key={`${l.port}:${l.pid}`} className={confirmKey === listenerKey(l) ? "confirming" : undefined}>
-                    <td>{l.port}</td>
-                    <td>
-                      {l.container_name ? (
-                        <>
-                          <span>{l.container_name}</span>
-                          <span className="muted"> container via {l.process_name ?? "proxy"}</span>
-                        </>
-                      ) : (
-                        l.process_name ?? "—"
-                      )}
-                    </td>
-                    <td className="muted">{l.pid}</td>
-                    <td className="muted">{formatUptime(l.started_seconds_ago)}</td>
-                    <td>
-                      <button
-                        className={`btn danger ${confirmKey === listenerKey(l) ? "confirm" : ""}`}
-                        onClick={() => disconnect(l)}
-                        disabled={disconnectingKey === listenerKey(l)}
-                      >
-                        {disconnectingKey === listenerKey(l)
-                          ? "Working..."
-                          : confirmKey === listenerKey(l)
-                            ? "Confirm"
-                            : l.container_name
-                              ? "Stop"
-                              : "Kill"}
-                      </button>
-                    </td>
-                  </tr>
+                {listenerGroups.map((group) => (
+                  <Fragment key={group.project ?? ""}>
+                    {group.project !== null ? (
+                      <tr className="group">
+                        <td colSpan={7}>
+                          compose project <strong>{group.project}</strong>
+                          {group.listeners[0].compose?.working_dir ? (
+                            <span className="path"> {group.listeners[0].compose.working_dir}</span>
+                          ) : null}
+                        </td>
+                      </tr>
+                    ) : null}
+                    {group.listeners.map((l) => (
+                      <tr key={listenerKey(l)} className={confirmKey === listenerKey(l) ? "confirming" : undefined}>
+                        <td>
+                          {l.port}
+                          <span className="muted">/{l.protocol}</span>
+                        </td>
+                        <td>
+                          {l.sockets.map((socket) => (
+                            <div key={socket.local_addr} className="muted" title={socketTitle(socket, l.protocol) || undefined}>
+                              {formatAddress(socket)}
+                              {socket.queue && socket.queue.received > 0 ? (
+                                <span className="small">
+                                  {" "}
+                                  {l.protocol === "tcp" ? `${socket.queue.received} waiting` : `${socket.queue.received} B queued`}
+                                </span>
+                              ) : null}
+                            </div>
+                          ))}
+                          <span className={`badge exposure-${l.exposure}`}>{EXPOSURE_LABELS[l.exposure]}</span>
+                        </td>
+                        <td title={processTitle(l) || undefined}>
+                          {l.container_name ? (
+                            <>
+                              <span>{l.container_name}</span>
+                              <span className="muted">
+                                {" "}
+                                {containerNoun(l.container_runtime)}
+                                {/* No bindings: found from the process, e.g. host networking. */}
+                                {l.container_bindings.length > 0 || l.processes.some((p) => p.proxy)
+                                  ? ` via ${processLabel(l) ?? "proxy"}`
+                                  : ` running ${processLabel(l) ?? "—"}`}
+                              </span>
+                              {l.compose ? <div className="muted">service {l.compose.service}</div> : null}
+                              {l.container_bindings.map((binding) => (
+                                <div key={`${binding.container_id}-${binding.host_ip}`} className="muted small">
+                                  {formatBinding(
+                                    binding,
+                                    new Set(l.container_bindings.map((b) => b.container_id)).size > 1
+                                  )}
+                                </div>
+                              ))}
+                            </>
+                          ) : (
+                            <>
+                              {processLabel(l) ?? "—"}
+                              {/* A proxy whose container no runtime reported. */}
+                              {l.processes.map((p) => {
+                                const forwardsTo = p.proxy ? proxyTarget(p.proxy) : null;
+                                return forwardsTo ? (
+                                  <div key={p.pid} className="muted small">
+                                    → {forwardsTo}
+                                  </div>
+                                ) : null;
+                              })}
+                            </>
+                          )}
+                          {l.processes.length > 1 ? <div className="muted">{l.processes.length} processes</div> : null}
+                          {distinctProjects(l).map((project) => (
+                            <div key={project.root} title={project.root}>
+                              <span className="badge">{project.name}</span>
+                              {project.git_branch ? <span className="muted"> ⎇ {project.git_branch}</span> : null}
+                              <div className="muted path">{project.root}</div>
+                            </div>
+                          ))}
+                          {distinctCwds(l).map((cwd) => (
+                            <div key={cwd} className="muted path">
+                              {cwd}
+                            </div>
+                          ))}
+                        </td>
+                        <td className="muted">
+                          {l.processes.map((p) => p.pid).join(", ") || "—"}
+                          {Array.from(new Set(l.processes.map((p) => p.user).filter(Boolean))).map((user) => (
+                            <div key={user}>{user}</div>
+                          ))}
+                        </td>
+                        <td title={peerTitle(l.connections) || undefined}>
+                          {l.protocol === "udp" ? (
+                            <span className="muted">—</span>
+                          ) : (
+                            <>
+                              <span>{l.connections.established}</span>
+                              {l.connections.time_wait + l.connections.close_wait > 0 ? (
+                                <span className="muted">
+                                  {" "}
+                                  +{l.connections.time_wait} tw / {l.connections.close_wait} cw
+                                </span>
+                              ) : null}
+                              {describePeers(l.connections) ? (
+                                <div className="muted">{describePeers(l.connections)}</div>
+                              ) : null}
+                            </>
+                          )}
+                        </td>
+                        <td className="muted">
+                          {formatListening(l)}
+                          <div className="small">process started {formatUptime(oldestStart(l))}</div>
+                        </td>
+                        <td>
+                          <button
+                            className={`btn danger ${confirmKey === listenerKey(l) ? "confirm" : ""}`}
+                            onClick={() => disconnect(l)}
+                            disabled={disconnectingKey === listenerKey(l)}
+                          >
+                            {disconnectingKey === listenerKey(l)
+                              ? "Working..."
+                              : confirmKey === listenerKey(l)
+                                ? "Confirm"
+                                : l.container_name
+                                  ? "Stop"
+                                  : "Kill"}
+                          </button>
+                        </td>
+                      </tr>
+                    ))}
+                  </Fragment>
                 ))}
               </tbody>
             </table>
           )}
+
+          {recentlyKilled.length + recentlyStopped.length > 0 && (
+            <>
+              <h2 className="section">Recently Killed</h2>
+              <table className="table">
+                <tbody>
+                  {recentlyStopped.map((container) => (
+                    <tr key={container.id}>
+                      <td>
+                        {container.name}
+                        <div className="muted small">{containerNoun(container.runtime)}</div>
+                      </td>
+                      <td>{container.listening.map((l) => `${l.port}/${l.protocol}`).join(", ") || "—"}</td>
+                      <td className="muted">{formatUptime(Math.max(0, Math.floor(Date.now() / 1000) - container.stopped_at))}</td>
+                      <td style={{ width: 90 }}>
+                        <button
+                          className="btn"
+                          onClick={() => restartContainer(container)}
+                          disabled={restartingId === container.id}
+                        >
+                          {restartingId === container.id ? "Working..." : "Start"}
+                        </button>
+                      </td>
+                    </tr>
+                  ))}
+                  {recentlyKilled.map((killed) => (
+                    <tr key={killed.id}>
+                      <td title={killed.command_line.join(" ")}>
+                        {killed.process_name}
+                        <div className="muted small path">{killed.cwd}</div>
+                      </td>
+                      <td>
+                        {killed.listening.map((l) => `${l.port}/${l.protocol}`).join(", ") || "—"}
+                      </td>
+                      <td className="muted">{formatUptime(Math.max(0, Math.floor(Date.now() / 1000) - killed.killed_at))}</td>
+                      <td style={{ width: 90 }}>
+                        <button
+                          className="btn"
+                          onClick={() => restartKilled(killed)}
+                          disabled={restartingId === killed.id}
+                        >
+                          {restartingId === killed.id ? "Working..." : "Restart"}
+                        </button>
+                      </td>
+                    </tr>
+                  ))}
+                </tbody>
+              </table>
+            </>
+          )}
         </div>
       </div>
     </div>
@@ -23,3 +23,102 @@ export function saveRanges(ranges: PortRange[]) {
   localStorage.setItem(KEY, JSON.stringify(ranges));
 }
 
+export type KillScope = "process" | "tree" | "group";
+
+const KILL_SCOPE_KEY = "port_o_potty_kill_scope_v1";
+
+export function loadKillScope(): KillScope {
+  const raw = localStorage.getItem(KILL_SCOPE_KEY);
+  return raw === "tree" || raw === "group" ? raw : "process";
+}
+
+export function saveKillScope(scope: KillScope) {
+  localStorage.setItem(KILL_SCOPE_KEY, scope);
+}
+
+export type ContainerScope = "container" | "service" | "project";
+
+const CONTAINER_SCOPE_KEY = "port_o_potty_container_scope_v1";
+
+export function loadContainerScope(): ContainerScope {
+  const raw = localStorage.getItem(CONTAINER_SCOPE_KEY);
+  return raw === "service" || raw === "project" ? raw : "container";
+}
+
+export function saveContainerScope(scope: ContainerScope) {
+  localStorage.setItem(CONTAINER_SCOPE_KEY, scope);
+}
+
+export type Signal = "INT" | "TERM" | "HUP" | "QUIT" | "KILL";
+export type EscalationStep = { signal: Signal; timeout_ms: number };
+export type EscalationPolicy = { steps: EscalationStep[]; port_close_timeout_ms: number };
+export type EscalationRule = { pattern: string; policy: EscalationPolicy };
+
+const ESCALATION_RULES_KEY = "port_o_potty_escalation_rules_v1";
+
+export function loadEscalationRules(): EscalationRule[] {
+  try {
+    const raw = localStorage.getItem(ESCALATION_RULES_KEY);
+    if (!raw) return [];
+    const parsed = JSON.parse(raw) as unknown;
+    if (!Array.isArray(parsed)) throw new Error("invalid");
+    return parsed.filter(
+      (r) => typeof (r as any)?.pattern === "string" && Array.isArray((r as any)?.policy?.steps)
+    ) as EscalationRule[];
+  } catch {
+    return [];
+  }
+}
+
+export function saveEscalationRules(rules: EscalationRule[]) {
+  localStorage.setItem(ESCALATION_RULES_KEY, JSON.stringify(rules));
+}
+
+export type ProtectedKind = "pid" | "name" | "exe_path" | "user";
+export type ProtectedEntry =
+  | { kind: "pid"; value: number }
+  | { kind: "name" | "exe_path" | "user"; value: string };
+
+const PROTECTED_KEY = "port_o_potty_protected_v1";
+
+export function loadProtected(): ProtectedEntry[] {
+  try {
+    const raw = localStorage.getItem(PROTECTED_KEY);
+    if (!raw) return [];
+    const parsed = JSON.parse(raw) as unknown;
+    if (!Array.isArray(parsed)) throw new Error("invalid");
+    return parsed.filter((p) =>
+      (p as any)?.kind === "pid"
+        ? Number.isInteger((p as any).value)
+        : ["name", "exe_path", "user"].includes((p as any)?.kind) && typeof (p as any).value === "string"
+    ) as ProtectedEntry[];
+  } catch {
+    return [];
+  }
+}
+
+export function saveProtected(entries: ProtectedEntry[]) {
+  localStorage.setItem(PROTECTED_KEY, JSON.stringify(entries));
+}
+
+const MONITOR_INTERVAL_KEY = "port_o_potty_monitor_interval_v1";
+
+export function loadMonitorInterval(): number {
+  const ms = Number(localStorage.getItem(MONITOR_INTERVAL_KEY));
+  return Number.isFinite(ms) && ms >= 1000 ? ms : 5000;
+}
+
+export function saveMonitorInterval(ms: number) {
+  localStorage.setItem(MONITOR_INTERVAL_KEY, String(ms));
+}
+
+const RUNTIME_TIMEOUT_KEY = "port_o_potty_runtime_timeout_v1";
+
+export function loadRuntimeTimeout(): number {
+  const ms = Number(localStorage.getItem(RUNTIME_TIMEOUT_KEY));
+  return Number.isFinite(ms) && ms >= 250 ? ms : 5000;
+}
+
+export function saveRuntimeTimeout(ms: number) {
+  localStorage.setItem(RUNTIME_TIMEOUT_KEY, String(ms));
+}